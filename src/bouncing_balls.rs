@@ -3,98 +3,370 @@ extern crate piston_window;
 extern crate quick_xml;
 extern crate rand;
 extern crate serde;
+extern crate serde_json;
+extern crate toml;
 
 mod geometry;
 mod utils;
 
-use geometry::ball::{Ball, BallSettings, BallUpdateArgs};
+use geometry::ball::{Ball, BallEvent, BallSettings, BallUpdateArgs};
 use geometry::vector2d::Vector2d;
 use geometry::velocity::Velocity;
 use piston_window::*;
-use std::option::Option::Some;
-use std::time::SystemTime;
-use utils::entity::{Renderable, Updatable};
+use rand::rngs::StdRng;
+use std::cell::Cell;
+use std::rc::Rc;
+use utils::canvas::Canvas;
+use utils::config::{GameConfig, WindowConfig};
+use utils::debug_overlay::DebugInfo;
+use utils::ecs::{DeltaTime, RenderableSystem, Schedule, ScreenSize, UpdatableSystem, World};
+use utils::event_bus::EventBus;
 use utils::game_window::GameWindow;
-use utils::input::InputHandler;
+use utils::headless::{headless_ticks, is_headless, run_headless};
+use utils::input::{GameInput, InputHandler};
+use utils::replay::{run_replay_headless, InputRecorder};
 use utils::scene::Scene;
+use utils::text_field::TextField;
 
 const WIDTH: u32 = 1280;
 const HEIGHT: u32 = 720;
 const CORNFLOWER_BLUE: [f32; 4] = [0.392, 0.584, 0.929, 1.0];
+const PROMPT_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const PROMPT_TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// u64::MAX has 20 digits
+const MAX_SEED_LENGTH: usize = 20;
 
-#[derive(Clone)]
 pub struct BouncingBalls {
-    balls: Vec<Ball>,
-    width: u32,
-    height: u32,
-    last_update: SystemTime,
+    world: World,
+    schedule: Schedule,
+    collisions: Rc<Cell<u32>>,
+    input: GameInput,
+    settings: BallSettings,
+    rng: StdRng,
+    next_id: u32,
+    config: GameConfig,
+    // Tab opens it, Enter restarts the demo with the typed seed, Tab again closes it
+    seed_prompt: Option<TextField>,
 }
 
 impl BouncingBalls {
-    pub fn new(width: u32, height: u32) -> BouncingBalls {
+    pub fn new(config: &GameConfig) -> BouncingBalls {
+        let width = config.window.width;
+        let height = config.window.height;
+        let mut events: EventBus<BallEvent> = EventBus::new();
+        let collisions = Rc::new(Cell::new(0));
+        let collision_counter = collisions.clone();
+        events.subscribe(move |event| match event {
+            BallEvent::Collision { .. } => collision_counter.set(collision_counter.get() + 1),
+        });
+
+        let mut world = World::new();
+        world.insert_resource(DeltaTime(0.0));
+        world.insert_resource(ScreenSize { width, height });
+        world.insert_resource(events);
+        world.register::<Ball>();
+        let mut rng = config.rng();
+        let mut settings = BallSettings::from_config(&config.balls);
+        settings.set_max_pos(width as f64, height as f64);
+        for id in 0..config.balls.count {
+            let ball = world.spawn();
+            world.insert(ball, settings.build_with_rng(id, &mut rng));
+        }
+
+        // every ball collides with the positions the others had at the start of the tick
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(UpdatableSystem::<Ball, _>::new(|world: &World| {
+                let screen = *world.resource::<ScreenSize>();
+                BallUpdateArgs {
+                    dt: world.resource::<DeltaTime>().0,
+                    width: screen.width,
+                    height: screen.height,
+                    balls: Some(world.read::<Ball>().iter().map(|(_, b)| *b).collect()),
+                    events: Some(world.resource::<EventBus<BallEvent>>().sender()),
+                }
+            }))
+            .add_render_system(RenderableSystem::<Ball>::new());
+
         BouncingBalls {
-            balls: (0..100)
-                .map(|id| {
-                    BallSettings::new()
-                        .set_max_pos(WIDTH as f64, HEIGHT as f64)
-                        .build(id)
-                })
-                .collect(),
-            width,
-            height,
-            last_update: SystemTime::now(),
+            world,
+            schedule,
+            collisions,
+            input: GameInput::new(),
+            settings,
+            rng,
+            next_id: config.balls.count,
+            config: config.clone(),
+            seed_prompt: None,
+        }
+    }
+
+    fn restart(&mut self, seed: u64) {
+        let screen = *self.world.resource::<ScreenSize>();
+        let mut config = self.config.clone();
+        config.seed = Some(seed);
+        config.window.width = screen.width;
+        config.window.height = screen.height;
+        *self = BouncingBalls::new(&config);
+    }
+
+    // Returns true if the prompt used the event
+    fn on_prompt_button_event(&mut self, args: ButtonArgs) -> bool {
+        let pressed = args.state == ButtonState::Press;
+        let prompt = match self.seed_prompt {
+            Some(ref mut prompt) => prompt,
+            None => {
+                if pressed && args.button == Button::Keyboard(Key::Tab) {
+                    let mut prompt = TextField::new();
+                    prompt.set_max_length(MAX_SEED_LENGTH);
+                    self.seed_prompt = Some(prompt);
+                    return true;
+                }
+                return false;
+            }
+        };
+        if prompt.on_button_event(args) || !pressed {
+            return true;
+        }
+        match args.button {
+            Button::Keyboard(Key::Return) => match prompt.text().parse() {
+                Ok(seed) => self.restart(seed),
+                Err(_) => println!("Invalid seed: {}", prompt.text()),
+            },
+            Button::Keyboard(Key::Tab) => self.seed_prompt = None,
+            _ => {}
         }
+        true
+    }
+
+    fn spawn_ball(&mut self, pos: [f64; 2]) {
+        let mut ball = self.settings.build_with_rng(self.next_id, &mut self.rng);
+        ball.pos = Vector2d {
+            x: pos[0],
+            y: pos[1],
+        };
+        ball.prev_pos = ball.pos;
+        self.next_id += 1;
+
+        let entity = self.world.spawn();
+        self.world.insert(entity, ball);
     }
 }
 
 impl Scene for BouncingBalls {
-    fn render(self, c: Context, g: &mut G2d) {
-        clear(CORNFLOWER_BLUE, g);
-        self.balls.iter().for_each(|ball| ball.render(c, g));
-    }
-
-    fn update(&mut self) {
-        let duration = SystemTime::now().duration_since(self.last_update).unwrap();
-        let w = self.width;
-        let h = self.height;
-        let dt = duration.as_secs_f64();
-
-        let ball_count = self.balls.len();
-        for i in 0..ball_count {
-            let balls = self.balls.to_vec();
-            let ball = &mut self.balls[i];
-
-            let update_args = BallUpdateArgs {
-                dt,
-                width: w,
-                height: h,
-                balls: Some(balls),
-            };
-            ball.update(update_args);
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        g.clear(CORNFLOWER_BLUE);
+        self.schedule.render(&self.world, c, g, alpha);
+
+        if let Some(ref prompt) = self.seed_prompt {
+            let height = self.world.resource::<ScreenSize>().height as f64;
+            g.rectangle(
+                PROMPT_BACKGROUND,
+                [0.0, height - 30.0, 300.0, 30.0],
+                c.transform,
+            );
+            g.text(
+                PROMPT_TEXT,
+                16,
+                &format!("seed: {}_", prompt.text()),
+                c.transform.trans(8.0, height - 9.0),
+            );
         }
+    }
 
-        self.last_update = SystemTime::now();
+    fn update(&mut self, dt: f64) {
+        // left click spawns a new ball under the cursor
+        if self.input.is_mouse_just_pressed(MouseButton::Left) {
+            if let Some(pos) = self.input.cursor_pos() {
+                self.spawn_ball(pos);
+            }
+        }
+
+        *self.world.resource_mut::<DeltaTime>() = DeltaTime(dt);
+        self.schedule.run(&self.world);
+        self.world.resource_mut::<EventBus<BallEvent>>().dispatch();
     }
 
     fn on_resize(&mut self, new_width: u32, new_height: u32) {
-        self.width = new_width;
-        self.height = new_height;
+        *self.world.resource_mut::<ScreenSize>() = ScreenSize {
+            width: new_width,
+            height: new_height,
+        };
+    }
+
+    fn debug_info(&self, info: &mut DebugInfo) {
+        info.add("balls", self.world.read::<Ball>().len());
+        info.add("collisions", self.collisions.get());
     }
 }
 
 impl InputHandler for BouncingBalls {
-    fn on_button_event(&mut self, _args: ButtonArgs) {}
+    fn on_button_event(&mut self, args: ButtonArgs) {
+        if !self.on_prompt_button_event(args) {
+            self.input.on_button_event(args);
+        }
+    }
+
+    fn on_move_event(&mut self, motion: Motion) {
+        self.input.on_move_event(motion);
+    }
+
+    fn on_text_event(&mut self, text: String) {
+        if let Some(ref mut prompt) = self.seed_prompt {
+            prompt.on_text_event(&text);
+        }
+    }
+
+    fn end_tick(&mut self, dt: f64) {
+        self.input.end_tick(dt);
+    }
 }
 
 fn main() {
-    let window = WindowSettings::new("bouncing-balls", [WIDTH, HEIGHT])
-        .graphics_api(OpenGL::V3_2)
-        .exit_on_esc(true)
-        .build()
-        .unwrap();
+    let config = GameConfig::load(&WindowConfig::new("bouncing-balls", WIDTH, HEIGHT));
+    let width = config.window.width;
+    let height = config.window.height;
+
+    // `--record <file>` / `--replay <file>` record the input of the game or play it back
+    let app = InputRecorder::new(BouncingBalls::new(&config), &config);
+
+    if is_headless() {
+        if app.is_replaying() {
+            run_replay_headless(app, width, height);
+        }
+        run_headless(app, width, height, headless_ticks().unwrap_or(0));
+        return;
+    }
 
-    let app = BouncingBalls::new(WIDTH, HEIGHT);
+    let window = config.window_settings().build().unwrap();
 
     let mut game_window = GameWindow::new(window, app);
+    game_window.debug_overlay().configure(&config.debug);
     game_window.game_loop();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::canvas::DrawCommand;
+    use utils::headless::HeadlessRunner;
+    use utils::replay::replay_headless;
+
+    fn runner(count: u32) -> HeadlessRunner<BouncingBalls> {
+        let mut config = GameConfig::new(&WindowConfig::new("bouncing-balls", WIDTH, HEIGHT));
+        config.balls.count = count;
+        config.seed = Some(42);
+        HeadlessRunner::new(BouncingBalls::new(&config), WIDTH, HEIGHT)
+    }
+
+    // The rects of the balls drawn in the frame
+    fn ball_rects(commands: &[DrawCommand]) -> Vec<[f64; 4]> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                DrawCommand::Ellipse { rect, .. } => Some(*rect),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn is_on_screen(rect: &[f64; 4]) -> bool {
+        let center = [rect[0] + rect[2] / 2.0, rect[1] + rect[3] / 2.0];
+        (0.0..=WIDTH as f64).contains(&center[0]) && (0.0..=HEIGHT as f64).contains(&center[1])
+    }
+
+    #[test]
+    fn draws_every_ball() {
+        let mut runner = runner(25);
+        assert_eq!(ball_rects(runner.render(1.0)).len(), 25);
+
+        runner.run(120);
+        assert_eq!(ball_rects(runner.render(1.0)).len(), 25);
+    }
+
+    #[test]
+    fn balls_move_and_stay_on_screen() {
+        let mut runner = runner(25);
+        let start = ball_rects(runner.render(1.0));
+
+        for _ in 0..10 {
+            runner.run(30);
+            let rects = ball_rects(runner.render(1.0));
+            assert!(rects.iter().all(is_on_screen), "{:?}", rects);
+        }
+        assert_ne!(ball_rects(runner.render(1.0)), start);
+    }
+
+    #[test]
+    fn click_spawns_a_ball_under_the_cursor() {
+        let mut runner = runner(5);
+        runner
+            .move_cursor(300.0, 200.0)
+            .press(Button::Mouse(MouseButton::Left))
+            .run(1)
+            .release(Button::Mouse(MouseButton::Left));
+
+        let rects = ball_rects(runner.render(0.0));
+        assert_eq!(rects.len(), 6);
+        let spawned = rects.iter().any(|r| {
+            (r[0] + r[2] / 2.0 - 300.0).abs() < 1e-6 && (r[1] + r[3] / 2.0 - 200.0).abs() < 1e-6
+        });
+        assert!(spawned, "{:?}", rects);
+    }
+
+    // Recorded with real input, the replay has to end up in the recorded state
+    #[test]
+    fn replays_the_recording() {
+        let mut config = GameConfig::new(&WindowConfig::new("bouncing-balls", WIDTH, HEIGHT));
+        config.set_replay("tests/replays/bouncing_balls.json");
+        let recorder = InputRecorder::new(BouncingBalls::new(&config), &config);
+        assert!(recorder.is_replaying());
+
+        let runner = replay_headless(recorder, config.window.width, config.window.height);
+        assert!(runner.ticks() > 0);
+        assert_eq!(runner.scene().matches_recording(), Some(true));
+    }
+
+    fn tap(runner: &mut HeadlessRunner<BouncingBalls>, key: Key) {
+        runner.press_key(key).release_key(key);
+    }
+
+    #[test]
+    fn seed_prompt_restarts_with_the_typed_seed() {
+        let mut runner = runner(5);
+        runner.run(30);
+        tap(&mut runner, Key::Tab);
+        runner.text("1").text("7");
+        tap(&mut runner, Key::Backspace);
+        runner.text("3");
+        assert!(runner.scene().seed_prompt.is_some());
+
+        tap(&mut runner, Key::Return);
+        assert!(runner.scene().seed_prompt.is_none());
+
+        let mut config = GameConfig::new(&WindowConfig::new("bouncing-balls", WIDTH, HEIGHT));
+        config.balls.count = 5;
+        config.seed = Some(13);
+        let mut expected = HeadlessRunner::new(BouncingBalls::new(&config), WIDTH, HEIGHT);
+        assert_eq!(
+            ball_rects(runner.render(1.0)),
+            ball_rects(expected.render(1.0))
+        );
+    }
+
+    #[test]
+    fn seed_prompt_keeps_invalid_seeds_open() {
+        let mut runner = runner(5);
+        let start = ball_rects(runner.render(1.0));
+        tap(&mut runner, Key::Tab);
+        runner.text("-1");
+        tap(&mut runner, Key::Return);
+        assert!(runner.scene().seed_prompt.is_some());
+
+        // closing it changes nothing, and text is ignored again
+        tap(&mut runner, Key::Tab);
+        runner.text("5");
+        assert!(runner.scene().seed_prompt.is_none());
+        assert_eq!(ball_rects(runner.render(1.0)), start);
+    }
+}
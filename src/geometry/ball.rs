@@ -1,6 +1,10 @@
-use piston_window::{ellipse, Context, G2d};
+use piston_window::Context;
+use rand::Rng;
 use std::option::Option::Some;
+use utils::canvas::Canvas;
+use utils::config::BallConfig;
 use utils::entity::{Renderable, Updatable};
+use utils::event_bus::EventSender;
 use Vector2d;
 use Velocity;
 
@@ -9,6 +13,7 @@ pub struct Ball {
     pub id: u32,
     pub velocity: Velocity,
     pub pos: Vector2d,
+    pub prev_pos: Vector2d,
     pub radius: f64,
     pub color: [f32; 4],
 }
@@ -43,33 +48,38 @@ impl Ball {
         }
     }
 
+    pub fn interpolated_pos(&self, alpha: f64) -> Vector2d {
+        self.prev_pos + (self.pos - self.prev_pos) * alpha
+    }
+
     pub fn collides_with_ball(self, b: &Ball) -> bool {
         (b.id != self.id) && (b.pos - self.pos).length() <= (b.radius + self.radius)
     }
 }
 
 impl Renderable for Ball {
-    fn render(self, c: Context, g: &mut G2d) {
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        let pos = self.interpolated_pos(alpha);
         let rect: [f64; 4] = [
-            self.pos.x - self.radius,
-            self.pos.y - self.radius,
+            pos.x - self.radius,
+            pos.y - self.radius,
             self.radius * 2.0,
             self.radius * 2.0,
         ];
-        ellipse(self.color, rect, c.transform, g);
+        g.ellipse(self.color, rect, c.transform);
     }
 }
 
 impl Updatable for Ball {
     type Args = BallUpdateArgs;
 
-    fn update(&mut self, args: Self::Args) {
+    fn update(&mut self, args: &Self::Args) {
+        self.prev_pos = self.pos;
         self.mv(args.dt);
         self.update_wall_collision(args.width, args.height);
 
-        if let Some(balls) = args.balls {
-            for j in 0..balls.len() {
-                let b = balls[j];
+        if let Some(ref balls) = args.balls {
+            for &b in balls {
                 if !self.collides_with_ball(&b) {
                     continue;
                 }
@@ -80,6 +90,13 @@ impl Updatable for Ball {
                 let mut n = b.pos - self.pos;
                 n.normalize();
                 self.velocity.reflect(&n);
+
+                if let Some(events) = &args.events {
+                    events.publish(BallEvent::Collision {
+                        id: self.id,
+                        other: b.id,
+                    });
+                }
             }
         }
     }
@@ -91,6 +108,12 @@ pub struct BallUpdateArgs {
     pub width: u32,
     pub height: u32,
     pub balls: Option<Vec<Ball>>,
+    pub events: Option<EventSender<BallEvent>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BallEvent {
+    Collision { id: u32, other: u32 },
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -118,6 +141,14 @@ impl BallSettings {
         }
     }
 
+    pub fn from_config(config: &BallConfig) -> BallSettings {
+        let mut settings = BallSettings::new();
+        settings
+            .set_velocity(config.min_velocity, config.max_velocity)
+            .set_radius(config.min_radius, config.max_radius);
+        settings
+    }
+
     pub fn set_velocity(&mut self, min: f64, max: f64) -> &mut BallSettings {
         self.min_velocity = min;
         self.max_velocity = max;
@@ -137,30 +168,37 @@ impl BallSettings {
     }
 
     pub fn build(self, id: u32) -> Ball {
+        self.build_with_rng(id, &mut rand::thread_rng())
+    }
+
+    pub fn build_with_rng<R: Rng + ?Sized>(self, id: u32, rng: &mut R) -> Ball {
         let mut dir = Vector2d {
-            x: rand::random::<f64>() * 2.0 - 1.0,
-            y: rand::random::<f64>() * 2.0 - 1.0,
+            x: rng.gen::<f64>() * 2.0 - 1.0,
+            y: rng.gen::<f64>() * 2.0 - 1.0,
         };
         dir.normalize();
 
         let color = if let Some(c) = self.color {
             c
         } else {
-            [rand::random(), rand::random(), rand::random(), 1.0]
+            [rng.gen(), rng.gen(), rng.gen(), 1.0]
+        };
+
+        let pos = Vector2d {
+            x: rng.gen::<f64>() * self.max_x,
+            y: rng.gen::<f64>() * self.max_y,
         };
 
         Ball {
             id,
             velocity: Velocity {
                 dir,
-                speed: rand::random::<f64>() * (self.max_velocity - self.min_velocity)
+                speed: rng.gen::<f64>() * (self.max_velocity - self.min_velocity)
                     + self.min_velocity,
             },
-            pos: Vector2d {
-                x: rand::random::<f64>() * self.max_x,
-                y: rand::random::<f64>() * self.max_y,
-            },
-            radius: rand::random::<f64>() * (self.max_radius - self.min_radius) + self.min_radius,
+            pos,
+            prev_pos: pos,
+            radius: rng.gen::<f64>() * (self.max_radius - self.min_radius) + self.min_radius,
             color,
         }
     }
@@ -80,7 +80,7 @@ impl Vector2d {
     pub fn reflect(&mut self, n: &Vector2d) {
         let dot_product = (*self) * (*n);
         let a = (*n) * 2.0 * dot_product;
-        self.x = self.x - a.x;
-        self.y = self.y - a.y;
+        self.x -= a.x;
+        self.y -= a.y;
     }
 }
@@ -0,0 +1,40 @@
+use piston::Key;
+use serde::{Deserialize, Serialize};
+use utils::action_map::ActionMap;
+
+pub const BINDINGS_FILE: &str = "bindings.toml";
+
+// Gamepad buttons and axes as numbered by SDL's game controller layout
+const GAMEPAD_A: u8 = 0;
+const GAMEPAD_START: u8 = 6;
+const GAMEPAD_DPAD_LEFT: u8 = 13;
+const GAMEPAD_DPAD_RIGHT: u8 = 14;
+const GAMEPAD_LEFT_X: u8 = 0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Pause,
+}
+
+pub fn default_bindings() -> ActionMap<Action> {
+    let mut bindings = ActionMap::new();
+    bindings
+        .bind_key(Action::MoveLeft, Key::A)
+        .bind_key(Action::MoveLeft, Key::Left)
+        .bind_gamepad_button(Action::MoveLeft, GAMEPAD_DPAD_LEFT)
+        .bind_gamepad_axis(Action::MoveLeft, GAMEPAD_LEFT_X, false)
+        .bind_key(Action::MoveRight, Key::D)
+        .bind_key(Action::MoveRight, Key::Right)
+        .bind_gamepad_button(Action::MoveRight, GAMEPAD_DPAD_RIGHT)
+        .bind_gamepad_axis(Action::MoveRight, GAMEPAD_LEFT_X, true)
+        .bind_key(Action::Jump, Key::Space)
+        .bind_key(Action::Jump, Key::W)
+        .bind_key(Action::Jump, Key::Up)
+        .bind_gamepad_button(Action::Jump, GAMEPAD_A)
+        .bind_key(Action::Pause, Key::P)
+        .bind_gamepad_button(Action::Pause, GAMEPAD_START);
+    bindings
+}
@@ -0,0 +1,7 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    Jumped,
+    Landed,
+    TileEntered { x: u32, y: u32 },
+    FlagReached,
+}
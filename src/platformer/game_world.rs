@@ -1,93 +1,186 @@
-use piston_window::{clear, Context, G2d};
-use platformer::constants::BACKGROUND;
-use platformer::map::{Map, Tile};
-use platformer::player::{Player, PlayerUpdateArgs};
+use piston::{Button, ButtonArgs, Motion};
+use piston_window::Context;
+use platformer::actions::Action;
+use platformer::events::GameEvent;
+use platformer::map::Map;
+use platformer::player::Player;
+use platformer::stats::PlayStats;
+use platformer::systems::{
+    player_system, MapRenderSystem, PlayerRenderSystem, SpriteBatchRenderSystem, TileTracker,
+    TileTrackingSystem,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use utils::action_map::ActionMap;
+use utils::asset_error::AssetError;
+use utils::assets::Handle;
+use utils::canvas::Canvas;
+use utils::debug_overlay::DebugInfo;
+use utils::ecs::{DeltaTime, Schedule, ScreenSize, World};
 use utils::entity::*;
+use utils::event_bus::EventBus;
 use utils::input::GameInput;
+use utils::sprite_batch::SpriteBatch;
 use utils::sprite_sheet::SpriteSheet;
 
-#[derive(Clone)]
 pub struct GameWorld {
-    pub width: u32,
-    pub height: u32,
-    sprite_sheet: SpriteSheet,
-    player: Player,
-    pub input: GameInput,
-    map: Map,
+    world: World,
+    schedule: Schedule,
+    stats: Rc<RefCell<PlayStats>>,
+    level: Handle<Map>,
+    level_version: u32,
 }
 
 impl GameWorld {
-    pub fn new(width: u32, height: u32, sprite_sheet: SpriteSheet) -> GameWorld {
-        let player = Player::new(
-            sprite_sheet
-                .sprite_size(&"playerRed_stand.png".to_string())
-                .unwrap(),
-        );
-
-        GameWorld {
-            width,
-            height,
-            sprite_sheet,
-            player,
-            input: GameInput::new(),
-            map: Map::new(64.0),
-        }
+    pub fn new(
+        width: u32,
+        height: u32,
+        sprite_sheet: Handle<SpriteSheet>,
+        level: Handle<Map>,
+        bindings: ActionMap<Action>,
+    ) -> Result<GameWorld, AssetError> {
+        let mut map = level.get().clone();
+        let player = {
+            let sheet = sprite_sheet.get();
+            map.resolve_sprites(&sheet)?;
+            let mut player = Player::new(sheet.size(sheet.find("playerRed_stand.png")?));
+            player.resolve_sprites(&sheet)?;
+            player
+        };
+
+        let player_tile = map.player_pos(player.pos);
+
+        let mut events: EventBus<GameEvent> = EventBus::new();
+        let stats = Rc::new(RefCell::new(PlayStats::default()));
+        let stats_subscriber = stats.clone();
+        events.subscribe(move |event| stats_subscriber.borrow_mut().on_event(event));
+
+        let mut world = World::new();
+        world.insert_resource(DeltaTime(0.0));
+        world.insert_resource(ScreenSize { width, height });
+        world.insert_resource(sprite_sheet);
+        world.insert_resource(SpriteBatch::new());
+        world.insert_resource(map);
+        world.insert_resource(GameInput::new());
+        world.insert_resource(bindings);
+        world.insert_resource(events);
+
+        let player_entity = world.spawn();
+        world.insert(player_entity, player);
+        world.insert(player_entity, TileTracker(player_tile));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(player_system())
+            .add_system(TileTrackingSystem)
+            .add_render_system(MapRenderSystem)
+            .add_render_system(PlayerRenderSystem)
+            .add_render_system(SpriteBatchRenderSystem);
+
+        Ok(GameWorld {
+            world,
+            schedule,
+            stats,
+            level_version: level.version(),
+            level,
+        })
     }
 
-    fn render_sprite(&self, t: &Tile, c: Context, g: &mut G2d) {
-        let sprite_size = self.sprite_sheet.sprite_size(&t.sprite_name).unwrap();
-        self.sprite_sheet.render_sprite(
-            &t.sprite_name,
-            t.coords(self.height as f64, 64.0, sprite_size),
-            c,
-            g,
-        );
+    pub fn on_button_event(&mut self, args: ButtonArgs) {
+        self.world.resource_mut::<GameInput>().on_button_event(args);
     }
-}
 
-impl Renderable for GameWorld {
-    fn render(self, c: Context, g: &mut G2d) {
-        clear(BACKGROUND, g);
+    pub fn on_move_event(&mut self, motion: Motion) {
+        self.world.resource_mut::<GameInput>().on_move_event(motion);
+    }
 
-        self.map
-            .tiles
-            .iter()
-            .for_each(|t| self.render_sprite(t, c, g));
+    // Cursor position in the y-up coordinates the player and the map use
+    pub fn cursor_world_pos(&self) -> Option<[f64; 2]> {
+        let height = self.world.resource::<ScreenSize>().height;
+        self.world
+            .resource::<GameInput>()
+            .cursor_pos_y_up(height as f64)
+    }
+
+    // True if the button is bound to the action, e.g. to handle `Pause` outside of the world
+    pub fn is_bound(&self, action: Action, button: Button) -> bool {
+        self.world
+            .resource::<ActionMap<Action>>()
+            .is_bound(action, button)
+    }
+
+    pub fn bindings(&self) -> ActionMap<Action> {
+        self.world.resource::<ActionMap<Action>>().clone()
+    }
+
+    pub fn end_tick(&mut self, dt: f64) {
+        self.world.resource_mut::<GameInput>().end_tick(dt);
+    }
+
+    // Releases all keys, e.g. when the game is paused
+    pub fn reset_input(&mut self) {
+        *self.world.resource_mut::<GameInput>() = GameInput::new();
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        *self.world.resource_mut::<ScreenSize>() = ScreenSize { width, height };
+    }
 
-        self.map
-            .decorations
+    // Takes over the level when its file was reloaded, the player stays where it is
+    fn reload_level(&mut self) {
+        if self.level.version() == self.level_version {
+            return;
+        }
+        self.level_version = self.level.version();
+
+        let mut map = self.level.get().clone();
+        let resolved = {
+            let sprite_sheet = self.world.resource::<Handle<SpriteSheet>>();
+            let sprite_sheet = sprite_sheet.get();
+            map.resolve_sprites(&sprite_sheet)
+        };
+        match resolved {
+            Ok(()) => *self.world.resource_mut::<Map>() = map,
+            Err(e) => println!("Cannot reload the level: {}", e),
+        }
+    }
+
+    pub fn debug_info(&self, info: &mut DebugInfo) {
+        {
+            let map = self.world.resource::<Map>();
+            info.add("tiles", map.tiles.len());
+            info.add("decorations", map.decorations.len());
+        }
+        self.world
+            .read::<Player>()
             .iter()
-            .for_each(|t| self.render_sprite(t, c, g));
-        self.render_sprite(&self.map.flag, c, g);
-
-        let player_args = self.player.render_args();
-        let player_size = self.sprite_sheet.sprite_size(&player_args.sprite).unwrap();
-        self.sprite_sheet.render_sprite(
-            &player_args.sprite,
-            [
-                player_args.pos[0] - player_size[0] / 2.0,
-                self.height as f64 - player_args.pos[1] - player_args.size[1],
-            ],
-            c,
-            g,
-        );
+            .for_each(|(_, player)| player.debug_info(info));
+        if let Some(pos) = self.cursor_world_pos() {
+            info.add("cursor", format!("{:.0}, {:.0}", pos[0], pos[1]));
+        }
+        self.stats.borrow().debug_info(info);
+    }
+}
+
+impl Renderable for GameWorld {
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        self.schedule.render(&self.world, c, g, alpha);
     }
 }
 
 impl Updatable for GameWorld {
     type Args = GameWorldUpdateArgs;
 
-    fn update(&mut self, args: Self::Args) {
-        self.player.update(PlayerUpdateArgs {
-            dt: args.dt,
-            input: self.input.clone(),
-            screen_size: [self.width as f64, self.height as f64],
-            map: self.map.clone(),
-        });
-
-        let (player_x, player_y) = self.map.player_pos(self.player.pos);
-        if player_x == self.map.flag.x && player_y == self.map.flag.y {
-            self.map.flag_reached();
+    fn update(&mut self, args: &Self::Args) {
+        self.reload_level();
+        *self.world.resource_mut::<DeltaTime>() = DeltaTime(args.dt);
+        self.schedule.run(&self.world);
+
+        let events = self.world.resource_mut::<EventBus<GameEvent>>().dispatch();
+        for event in events {
+            if event == GameEvent::FlagReached {
+                self.world.resource_mut::<Map>().flag_reached();
+            }
         }
     }
 }
@@ -1,4 +1,12 @@
-use serde::export::Option::Some;
+use platformer::constants::TILE_SIZE;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use utils::asset_error::AssetError;
+use utils::assets::{Asset, LoadContext};
+use utils::sprite_sheet::{SpriteId, SpriteSheet};
+
+const FLAG_REACHED_SPRITE: &str = "flagGreen_up.png";
 
 #[derive(Debug, Clone)]
 pub struct Map {
@@ -6,6 +14,8 @@ pub struct Map {
     pub tiles: Vec<Tile>,
     pub decorations: Vec<Tile>,
     pub flag: Tile,
+    // The flag's sprite once it's reached, set by `resolve_sprites` like the tiles' sprites
+    flag_reached_sprite: Option<SpriteId>,
     is_flag_reached: bool,
 }
 
@@ -42,10 +52,42 @@ impl Map {
                 Tile::new_decoration("signArrow_right.png", 0, 3, true, 0.0, 0.0),
             ],
             flag: Tile::new_decoration("flagGreen_down.png", 4, 5, false, 0.3, -0.05),
+            flag_reached_sprite: None,
+            is_flag_reached: false,
+        }
+    }
+
+    // A map with only the given tiles, for tests that need a specific layout
+    #[cfg(test)]
+    pub fn with_tiles(tile_size: f64, tiles: Vec<Tile>) -> Map {
+        Map {
+            tile_size,
+            tiles,
+            decorations: Vec::new(),
+            flag: Tile::new_tile("flagGreen_down.png", 9, 9),
+            flag_reached_sprite: None,
             is_flag_reached: false,
         }
     }
 
+    // Level files are TOML with `tiles`, `decorations` and `flag` entries, see `assets/levels`
+    pub fn from_file(tile_size: f64, path: &Path) -> Result<Map, AssetError> {
+        let content = fs::read_to_string(path).map_err(|e| AssetError::not_found(path, e))?;
+        let level: Level = toml::from_str(&content).map_err(|e| {
+            let location = e.line_col().map(|(line, column)| (line + 1, column + 1));
+            AssetError::parse(path, location, e)
+        })?;
+
+        Ok(Map {
+            tile_size,
+            tiles: level.tiles,
+            decorations: level.decorations,
+            flag: level.flag,
+            flag_reached_sprite: None,
+            is_flag_reached: false,
+        })
+    }
+
     pub fn floor_under_position(&self, pos: [f64; 2]) -> f64 {
         let (x, y) = self.player_pos(pos);
         let highest_tile = self
@@ -109,34 +151,82 @@ impl Map {
         )
     }
 
+    // Looks up the sprites of all tiles once. Tiles missing from the sprite sheet are not
+    // drawn, unless the sprite sheet is strict, which makes them an error.
+    pub fn resolve_sprites(&mut self, sprite_sheet: &SpriteSheet) -> Result<(), AssetError> {
+        self.flag_reached_sprite = match sprite_sheet.find(FLAG_REACHED_SPRITE) {
+            Ok(id) => Some(id),
+            Err(e) if sprite_sheet.is_strict() => return Err(e),
+            Err(_) => None,
+        };
+        let tiles = self.tiles.iter_mut().chain(self.decorations.iter_mut());
+        for tile in tiles.chain(Some(&mut self.flag)) {
+            tile.sprite = match sprite_sheet.find(&tile.sprite_name) {
+                Ok(id) => Some(id),
+                Err(e) if sprite_sheet.is_strict() => return Err(e),
+                Err(_) => None,
+            };
+        }
+        Ok(())
+    }
+
     pub fn flag_reached(&mut self) {
         if !self.is_flag_reached {
             self.flag = Tile::new_decoration(
-                "flagGreen_up.png",
+                FLAG_REACHED_SPRITE,
                 self.flag.x,
                 self.flag.y,
                 false,
                 0.3,
                 -0.05,
             );
+            self.flag.sprite = self.flag_reached_sprite;
+            self.is_flag_reached = true;
         }
     }
 }
 
-#[derive(Debug, Clone)]
+impl Asset for Map {
+    fn load(path: &Path, _context: &mut LoadContext) -> Result<Map, AssetError> {
+        Map::from_file(TILE_SIZE, path)
+    }
+}
+
+#[derive(Deserialize)]
+struct Level {
+    tiles: Vec<Tile>,
+    #[serde(default)]
+    decorations: Vec<Tile>,
+    flag: Tile,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Tile {
     pub sprite_name: String,
+    // Set by `Map::resolve_sprites`, so rendering doesn't look the sprite up by name
+    #[serde(skip)]
+    pub sprite: Option<SpriteId>,
     pub x: u32,
     pub y: u32,
+    #[serde(default)]
     align_to_center: bool,
+    #[serde(default)]
     rat_x: f64,
+    #[serde(default)]
     rat_y: f64,
 }
 
 impl Tile {
+    // The resolved sprite, or the one named `sprite_name` if it wasn't resolved yet
+    pub fn sprite_id(&self, sprite_sheet: &SpriteSheet) -> Option<SpriteId> {
+        self.sprite
+            .or_else(|| sprite_sheet.sprite_id(&self.sprite_name))
+    }
+
     pub fn new_tile(sprite_name: &str, x: u32, y: u32) -> Tile {
         Tile {
             sprite_name: sprite_name.to_string(),
+            sprite: None,
             x,
             y,
             align_to_center: false,
@@ -155,6 +245,7 @@ impl Tile {
     ) -> Tile {
         Tile {
             sprite_name: sprite_name.to_string(),
+            sprite: None,
             x,
             y,
             align_to_center,
@@ -177,3 +268,35 @@ impl Tile {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite_sheet() -> SpriteSheet {
+        SpriteSheet::load(Path::new("assets/sprites.xml"), None, None).unwrap()
+    }
+
+    #[test]
+    fn reached_flag_keeps_its_sprite_resolved() {
+        let sprite_sheet = sprite_sheet();
+        let mut map = Map::new(TILE_SIZE);
+        map.resolve_sprites(&sprite_sheet).unwrap();
+        map.flag_reached();
+        assert_eq!(map.flag.sprite, sprite_sheet.sprite_id(FLAG_REACHED_SPRITE));
+        assert!(map.flag.sprite.is_some());
+    }
+
+    #[test]
+    fn strict_sprite_sheets_need_the_reached_flag() {
+        let mut sprite_sheet = sprite_sheet();
+        sprite_sheet.set_strict(true);
+        let mut map = Map::new(TILE_SIZE);
+        assert_eq!(map.resolve_sprites(&sprite_sheet), Ok(()));
+        map.flag.sprite_name = "missing.png".to_string();
+        assert_eq!(
+            map.resolve_sprites(&sprite_sheet),
+            Err(AssetError::unknown_sprite("missing.png"))
+        );
+    }
+}
@@ -1,4 +1,9 @@
+pub mod actions;
 pub mod constants;
+pub mod events;
 pub mod game_world;
 pub mod map;
+pub mod pause_screen;
 pub mod player;
+pub mod stats;
+pub mod systems;
@@ -0,0 +1,63 @@
+use piston::{ButtonArgs, ButtonState};
+use piston_window::Context;
+use platformer::actions::Action;
+use utils::action_map::ActionMap;
+use utils::canvas::Canvas;
+use utils::input::InputHandler;
+use utils::scene::Scene;
+use utils::scene_manager::SceneTransition;
+
+const DIM_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const ICON_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+
+#[derive(Default)]
+pub struct PauseScreen {
+    bindings: ActionMap<Action>,
+    resume_requested: bool,
+}
+
+impl PauseScreen {
+    // Resumes with the inputs bound to `Action::Pause`
+    pub fn new(bindings: ActionMap<Action>) -> PauseScreen {
+        PauseScreen {
+            bindings,
+            resume_requested: false,
+        }
+    }
+}
+
+impl Scene for PauseScreen {
+    fn render(&self, c: Context, g: &mut dyn Canvas, _alpha: f64) {
+        let [w, h] = c.get_view_size();
+        g.rectangle(DIM_COLOR, [0.0, 0.0, w, h], c.transform);
+
+        let (cx, cy) = (w / 2.0, h / 2.0);
+        g.rectangle(ICON_COLOR, [cx - 24.0, cy - 32.0, 16.0, 64.0], c.transform);
+        g.rectangle(ICON_COLOR, [cx + 8.0, cy - 32.0, 16.0, 64.0], c.transform);
+    }
+
+    fn update(&mut self, _dt: f64) {}
+
+    fn on_resize(&mut self, _new_width: u32, _new_height: u32) {}
+
+    fn transition(&mut self) -> Option<SceneTransition> {
+        if self.resume_requested {
+            self.resume_requested = false;
+            Some(SceneTransition::Pop)
+        } else {
+            None
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+impl InputHandler for PauseScreen {
+    fn on_button_event(&mut self, args: ButtonArgs) {
+        if self.bindings.is_bound(Action::Pause, args.button) && args.state == ButtonState::Press {
+            self.resume_requested = true;
+        }
+    }
+}
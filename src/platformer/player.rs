@@ -1,21 +1,32 @@
-use piston::Key;
+use platformer::actions::Action;
 use platformer::constants::*;
+use platformer::events::GameEvent;
 use platformer::map::Map;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use utils::action_map::ActionState;
+use utils::asset_error::AssetError;
+use utils::debug_overlay::DebugInfo;
 use utils::entity::Updatable;
-use utils::input::GameInput;
+use utils::event_bus::EventSender;
+use utils::sprite_sheet::{SpriteId, SpriteSheet};
 
 #[derive(Clone)]
 pub struct Player {
     pub size: [f64; 2],
     pub pos: [f64; 2],
+    prev_pos: [f64; 2],
     state: PlayerState,
     frame_ctr: usize,
-    frames: Vec<String>,
-    last_frame_time: SystemTime,
+    frames: &'static [&'static str],
+    // Set by `resolve_sprites`, so rendering doesn't look the frames up by name
+    sprites: HashMap<&'static str, SpriteId>,
+    frame_time: f64,
     action_timer: f64,
     speed: f64,
+    facing_left: bool,
     can_jump: bool,
+    jump_buffer: f64,
+    coyote_timer: f64,
 }
 
 impl Player {
@@ -23,42 +34,81 @@ impl Player {
         Player {
             size,
             pos: [32.0, 192.0],
+            prev_pos: [32.0, 192.0],
             state: PlayerState::Stand,
             frame_ctr: 0,
             frames: PlayerState::Stand.frames(),
-            last_frame_time: SystemTime::now(),
+            sprites: HashMap::new(),
+            frame_time: 0.0,
             action_timer: 0.0,
             speed: 0.0,
+            facing_left: false,
             can_jump: true,
+            jump_buffer: 0.0,
+            coyote_timer: 0.0,
         }
     }
 
-    pub fn render_args(&self) -> RenderArgs {
-        let sprite = self.frames[self.frame_ctr].clone();
+    // Looks up the sprites of all frames once, like `Map::resolve_sprites`. Frames missing from
+    // the sprite sheet are not drawn, unless the sprite sheet is strict.
+    pub fn resolve_sprites(&mut self, sprite_sheet: &SpriteSheet) -> Result<(), AssetError> {
+        self.sprites.clear();
+        let names = PlayerState::ALL.iter().flat_map(|state| state.frames());
+        for &name in names {
+            match sprite_sheet.find(name) {
+                Ok(id) => {
+                    self.sprites.insert(name, id);
+                }
+                Err(e) if sprite_sheet.is_strict() => return Err(e),
+                Err(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn render_args(&self, alpha: f64) -> RenderArgs {
         RenderArgs {
-            sprite,
-            pos: self.pos,
+            sprite: self.sprites.get(self.frames[self.frame_ctr]).cloned(),
+            pos: [
+                self.prev_pos[0] + (self.pos[0] - self.prev_pos[0]) * alpha,
+                self.prev_pos[1] + (self.pos[1] - self.prev_pos[1]) * alpha,
+            ],
             size: self.size,
+            flip: self.facing_left,
         }
     }
 
+    pub fn debug_info(&self, info: &mut DebugInfo) {
+        info.add("player state", format!("{:?}", self.state));
+        info.add(
+            "player pos",
+            format!("{:.1}, {:.1}", self.pos[0], self.pos[1]),
+        );
+        info.add("player speed", format!("{:.1}", self.speed));
+    }
+
+    fn jump(&mut self, events: &EventSender<GameEvent>) {
+        self.set_state(PlayerState::AscendStart);
+        self.action_timer = ASCEND_TIME;
+        self.jump_buffer = 0.0;
+        self.coyote_timer = 0.0;
+        events.publish(GameEvent::Jumped);
+    }
+
     fn set_state(&mut self, state: PlayerState) {
         self.frames = state.frames();
         self.state = state;
         self.frame_ctr = 0;
     }
 
-    fn update_frame(&mut self) {
-        let dt = SystemTime::now()
-            .duration_since(self.last_frame_time)
-            .unwrap()
-            .as_secs_f64();
-        if dt >= 1.0 / PLAYER_FPS as f64 {
+    fn update_frame(&mut self, dt: f64) {
+        self.frame_time += dt;
+        if self.frame_time >= 1.0 / PLAYER_FPS as f64 {
+            self.frame_time = 0.0;
             self.frame_ctr = (self.frame_ctr + 1) % self.frames.len();
             if self.state == PlayerState::AscendStart && self.frame_ctr == 0 {
                 self.set_state(PlayerState::Ascend)
             }
-            self.last_frame_time = SystemTime::now();
         }
     }
 }
@@ -66,30 +116,40 @@ impl Player {
 impl Updatable for Player {
     type Args = PlayerUpdateArgs;
 
-    fn update(&mut self, args: Self::Args) {
+    fn update(&mut self, args: &Self::Args) {
+        self.prev_pos = self.pos;
         let floor = args.map.floor_under_position(self.pos);
+        // holding the button doesn't jump again, every jump needs a fresh press, which is
+        // remembered for a while in case the player can't jump yet
+        if args.actions.is_just_pressed(Action::Jump) {
+            self.jump_buffer = JUMP_BUFFER_TIME;
+        }
+        let jump = self.jump_buffer > 0.0;
+        // between -1.0 and 1.0, analog sticks make the player run only as fast as far they
+        // are pushed
+        let direction = args.actions.axis(Action::MoveLeft, Action::MoveRight);
+        if direction != 0.0 {
+            self.facing_left = direction < 0.0;
+        }
         match self.state {
             PlayerState::Stand => {
-                if args.input.is_key_pressed(Key::Space) && self.can_jump {
-                    self.set_state(PlayerState::AscendStart);
-                    self.action_timer = ASCEND_TIME;
-                } else if args.input.is_key_pressed(Key::D) {
+                self.coyote_timer = COYOTE_TIME;
+                if jump && self.can_jump {
+                    self.jump(&args.events);
+                } else if direction != 0.0 {
                     self.set_state(PlayerState::Move);
-                    self.speed = (args.dt / SPEED_UP_TIME) * MAX_SPEED;
-                } else if args.input.is_key_pressed(Key::A) {
-                    self.set_state(PlayerState::Move);
-                    self.speed = (args.dt / SPEED_UP_TIME) * -MAX_SPEED;
+                    self.speed = (args.dt / SPEED_UP_TIME) * MAX_SPEED * direction;
                 }
-                if self.pos[1] > floor {
+                if self.state != PlayerState::AscendStart && self.pos[1] > floor {
                     self.set_state(PlayerState::Descend);
                 }
             }
             PlayerState::Move => {
-                if args.input.is_key_pressed(Key::Space) && self.can_jump {
-                    self.set_state(PlayerState::AscendStart);
-                    self.action_timer = ASCEND_TIME;
+                self.coyote_timer = COYOTE_TIME;
+                if jump && self.can_jump {
+                    self.jump(&args.events);
                 }
-                if self.pos[1] > floor {
+                if self.state != PlayerState::AscendStart && self.pos[1] > floor {
                     self.set_state(PlayerState::Descend);
                 }
             }
@@ -123,7 +183,11 @@ impl Updatable for Player {
                 }
             }
             PlayerState::Descend => {
-                if self.pos[1] > floor {
+                // coyote time, only after walking off a ledge since jumping resets the timer
+                if jump && self.can_jump && self.coyote_timer > 0.0 {
+                    self.jump(&args.events);
+                } else if self.pos[1] > floor {
+                    self.coyote_timer -= args.dt;
                     let desc_dt = args.dt * DESCEND_SPEED;
                     let desc_floor = self.pos[1] - floor;
                     self.pos[1] -= if desc_dt < desc_floor {
@@ -142,19 +206,17 @@ impl Updatable for Player {
                     }
                     self.action_timer = JUMP_COOL_DOWN;
                     self.can_jump = false;
+                    args.events.publish(GameEvent::Landed);
                 }
             }
         }
         let speed_change = (args.dt / SPEED_UP_TIME) * MAX_SPEED;
-        if args.input.is_key_pressed(Key::D) {
-            self.speed += speed_change;
-            if self.speed > MAX_SPEED {
-                self.speed = MAX_SPEED
-            }
-        } else if args.input.is_key_pressed(Key::A) {
-            self.speed -= speed_change;
-            if self.speed < -MAX_SPEED {
-                self.speed = -MAX_SPEED
+        if direction != 0.0 {
+            let max_speed = MAX_SPEED * direction;
+            if self.speed < max_speed {
+                self.speed = (self.speed + speed_change).min(max_speed);
+            } else {
+                self.speed = (self.speed - speed_change).max(max_speed);
             }
         } else {
             if self.speed < 0.0 {
@@ -202,18 +264,21 @@ impl Updatable for Player {
                 self.set_state(PlayerState::Stand);
             }
         }
-        self.update_frame();
+        self.jump_buffer = (self.jump_buffer - args.dt).max(0.0);
+        self.update_frame(args.dt);
     }
 }
 
+#[derive(Clone)]
 pub struct PlayerUpdateArgs {
     pub dt: f64,
-    pub input: GameInput,
+    pub actions: ActionState<Action>,
     pub screen_size: [f64; 2],
     pub map: Map,
+    pub events: EventSender<GameEvent>,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 enum PlayerState {
     Stand,
     Move,
@@ -224,29 +289,218 @@ enum PlayerState {
 }
 
 impl PlayerState {
-    pub fn frames(self) -> Vec<String> {
+    const ALL: [PlayerState; 6] = [
+        PlayerState::Stand,
+        PlayerState::Move,
+        PlayerState::AscendStart,
+        PlayerState::Ascend,
+        PlayerState::Float,
+        PlayerState::Descend,
+    ];
+
+    pub fn frames(self) -> &'static [&'static str] {
         match self {
-            PlayerState::Stand => vec!["playerRed_stand.png".to_string()],
-            PlayerState::Move => vec![
-                "playerRed_walk1.png".to_string(),
-                "playerRed_walk2.png".to_string(),
-                "playerRed_walk3.png".to_string(),
-                "playerRed_walk2.png".to_string(),
+            PlayerState::Stand => &["playerRed_stand.png"],
+            PlayerState::Move => &[
+                "playerRed_walk1.png",
+                "playerRed_walk2.png",
+                "playerRed_walk3.png",
+                "playerRed_walk2.png",
             ],
-            PlayerState::AscendStart => vec![
-                "playerRed_up1.png".to_string(),
-                "playerRed_up2.png".to_string(),
-            ],
-            PlayerState::Ascend => vec!["playerRed_up3.png".to_string()],
-            PlayerState::Float => vec!["playerRed_up3.png".to_string()],
-            PlayerState::Descend => vec!["playerRed_fall.png".to_string()],
+            PlayerState::AscendStart => &["playerRed_up1.png", "playerRed_up2.png"],
+            PlayerState::Ascend => &["playerRed_up3.png"],
+            PlayerState::Float => &["playerRed_up3.png"],
+            PlayerState::Descend => &["playerRed_fall.png"],
         }
     }
 }
 
 #[derive(Debug)]
 pub struct RenderArgs {
-    pub sprite: String,
+    // None if the frame's sprite wasn't resolved
+    pub sprite: Option<SpriteId>,
     pub pos: [f64; 2],
     pub size: [f64; 2],
+    // The sprites face right, they are mirrored when the player faces left
+    pub flip: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piston::Button::Keyboard;
+    use piston::{ButtonArgs, ButtonState, Key};
+    use platformer::actions::default_bindings;
+    use platformer::map::Tile;
+    use utils::action_map::ActionMap;
+    use utils::event_bus::EventBus;
+    use utils::input::GameInput;
+
+    const DT: f64 = 1.0 / 60.0;
+    const SCREEN_SIZE: [f64; 2] = [640.0, 448.0];
+
+    #[derive(Clone)]
+    struct Sim {
+        player: Player,
+        map: Map,
+        input: GameInput,
+        bindings: ActionMap<Action>,
+    }
+
+    impl Sim {
+        fn new(map: Map, pos: [f64; 2]) -> Sim {
+            let mut player = Player::new([39.0, 48.0]);
+            player.pos = pos;
+            player.prev_pos = pos;
+            Sim {
+                player,
+                map,
+                input: GameInput::new(),
+                bindings: default_bindings(),
+            }
+        }
+
+        fn button(&mut self, key: Key, state: ButtonState) {
+            self.input.on_button_event(ButtonArgs {
+                state,
+                button: Keyboard(key),
+                scancode: None,
+            });
+        }
+
+        // Returns true if the player started a jump during the tick
+        fn tick(&mut self) -> bool {
+            let was_jumping = self.player.state == PlayerState::AscendStart;
+            self.player.update(&PlayerUpdateArgs {
+                dt: DT,
+                actions: self.bindings.state(&self.input),
+                screen_size: SCREEN_SIZE,
+                map: self.map.clone(),
+                events: EventBus::new().sender(),
+            });
+            self.input.end_tick(DT);
+            !was_jumping && self.player.state == PlayerState::AscendStart
+        }
+
+        fn run(&mut self, ticks: u32) -> bool {
+            (0..ticks).fold(false, |jumped, _| self.tick() || jumped)
+        }
+
+        // Presses jump for a single tick
+        fn tap_jump(&mut self) -> bool {
+            self.button(Key::Space, ButtonState::Press);
+            let jumped = self.tick();
+            self.button(Key::Space, ButtonState::Release);
+            jumped
+        }
+
+        fn is_falling(&self) -> bool {
+            self.player.state == PlayerState::Descend
+        }
+
+        // Ticks it takes from now until the player stands on the ground again
+        fn ticks_until_landed(&self) -> u32 {
+            let mut sim = self.clone();
+            let mut ticks = 0;
+            while !sim.is_falling() {
+                sim.tick();
+                ticks += 1;
+            }
+            while sim.is_falling() {
+                sim.tick();
+                ticks += 1;
+            }
+            ticks
+        }
+
+        // Ticks it takes from now until the player walks off the ledge holding right
+        fn ticks_until_falling(&self) -> u32 {
+            let mut sim = self.clone();
+            let mut ticks = 0;
+            while !sim.is_falling() {
+                sim.tick();
+                ticks += 1;
+            }
+            ticks
+        }
+    }
+
+    fn ticks(time: f64) -> u32 {
+        (time / DT).round() as u32
+    }
+
+    // Open ground, with a 5 tiles high ledge on the left
+    fn ledge_map() -> Map {
+        let mut tiles = Vec::new();
+        for x in 0..3 {
+            for y in 0..5 {
+                tiles.push(Tile::new_tile("tileYellow_06.png", x, y));
+            }
+        }
+        Map::with_tiles(64.0, tiles)
+    }
+
+    // Falling from high up without having stood anywhere, so there's no coyote time
+    fn falling_player() -> Sim {
+        let mut sim = Sim::new(ledge_map(), [400.0, 300.0]);
+        sim.player.set_state(PlayerState::Descend);
+        sim
+    }
+
+    fn player_on_ledge() -> Sim {
+        let mut sim = Sim::new(ledge_map(), [100.0, 320.0]);
+        sim.button(Key::D, ButtonState::Press);
+        sim
+    }
+
+    #[test]
+    fn jump_pressed_slightly_before_landing_is_buffered() {
+        let sim = falling_player();
+        let landing = sim.ticks_until_landed();
+        for early in 1..3 {
+            let mut sim = sim.clone();
+            assert!(!sim.run(landing - early));
+            assert!(sim.is_falling());
+            assert!(!sim.tap_jump(), "jumped in the air");
+            assert!(
+                sim.run(ticks(JUMP_COOL_DOWN) + early + 1),
+                "pressed {} ticks before landing",
+                early
+            );
+        }
+    }
+
+    #[test]
+    fn jump_pressed_long_before_landing_is_dropped() {
+        let mut sim = falling_player();
+        let landing = sim.ticks_until_landed();
+        let early = ticks(JUMP_BUFFER_TIME) + ticks(JUMP_COOL_DOWN) + 2;
+        assert!(landing > early);
+        sim.run(landing - early);
+        assert!(!sim.tap_jump());
+        assert!(!sim.run(60));
+        assert!(!sim.is_falling());
+    }
+
+    #[test]
+    fn jump_pressed_slightly_after_leaving_a_ledge_is_allowed() {
+        let sim = player_on_ledge();
+        let falling = sim.ticks_until_falling();
+        for late in 1..3 {
+            let mut sim = sim.clone();
+            sim.run(falling + late);
+            assert!(sim.is_falling());
+            assert!(sim.tap_jump(), "pressed {} ticks after falling", late);
+        }
+    }
+
+    #[test]
+    fn jump_pressed_long_after_leaving_a_ledge_is_ignored() {
+        let mut sim = player_on_ledge();
+        let falling = sim.ticks_until_falling();
+        sim.run(falling + ticks(COYOTE_TIME) + 2);
+        assert!(sim.is_falling());
+        assert!(!sim.tap_jump());
+        assert!(!sim.run(60));
+    }
 }
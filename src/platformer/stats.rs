@@ -0,0 +1,28 @@
+use platformer::events::GameEvent;
+use utils::debug_overlay::DebugInfo;
+
+#[derive(Debug, Default)]
+pub struct PlayStats {
+    jumps: u32,
+    landings: u32,
+    tiles_entered: u32,
+    flags_reached: u32,
+}
+
+impl PlayStats {
+    pub fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::Jumped => self.jumps += 1,
+            GameEvent::Landed => self.landings += 1,
+            GameEvent::TileEntered { .. } => self.tiles_entered += 1,
+            GameEvent::FlagReached => self.flags_reached += 1,
+        }
+    }
+
+    pub fn debug_info(&self, info: &mut DebugInfo) {
+        info.add("jumps", self.jumps);
+        info.add("landings", self.landings);
+        info.add("tiles entered", self.tiles_entered);
+        info.add("flags reached", self.flags_reached);
+    }
+}
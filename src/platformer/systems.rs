@@ -0,0 +1,140 @@
+use piston_window::Context;
+use platformer::actions::Action;
+use platformer::constants::BACKGROUND;
+use platformer::events::GameEvent;
+use platformer::map::{Map, Tile};
+use platformer::player::{Player, PlayerUpdateArgs};
+use utils::action_map::ActionMap;
+use utils::assets::Handle;
+use utils::canvas::Canvas;
+use utils::ecs::{DeltaTime, RenderSystem, ScreenSize, System, UpdatableSystem, World};
+use utils::event_bus::EventBus;
+use utils::input::GameInput;
+use utils::sprite_batch::SpriteBatch;
+use utils::sprite_sheet::{DrawParams, SpriteSheet};
+
+// Tile the entity stood on during the previous tick
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TileTracker(pub (u32, u32));
+
+pub fn player_system() -> UpdatableSystem<Player, impl FnMut(&World) -> PlayerUpdateArgs> {
+    UpdatableSystem::new(|world: &World| {
+        let screen = *world.resource::<ScreenSize>();
+        PlayerUpdateArgs {
+            dt: world.resource::<DeltaTime>().0,
+            actions: world
+                .resource::<ActionMap<Action>>()
+                .state(&world.resource::<GameInput>()),
+            screen_size: [screen.width as f64, screen.height as f64],
+            map: world.resource::<Map>().clone(),
+            events: world.resource::<EventBus<GameEvent>>().sender(),
+        }
+    })
+}
+
+// Publishes `TileEntered` and `FlagReached` when a player moves onto another tile
+pub struct TileTrackingSystem;
+
+impl System for TileTrackingSystem {
+    fn run(&mut self, world: &World) {
+        let players = world.read::<Player>();
+        let mut trackers = world.write::<TileTracker>();
+        let map = world.resource::<Map>();
+        let events = world.resource::<EventBus<GameEvent>>();
+        for (entity, tracker) in trackers.iter_mut() {
+            let player = match players.get(entity) {
+                Some(player) => player,
+                None => continue,
+            };
+            let player_tile = map.player_pos(player.pos);
+            if player_tile != tracker.0 {
+                tracker.0 = player_tile;
+                let (x, y) = player_tile;
+                events.publish(GameEvent::TileEntered { x, y });
+                if x == map.flag.x && y == map.flag.y {
+                    events.publish(GameEvent::FlagReached);
+                }
+            }
+        }
+    }
+}
+
+// Clears the screen and queues the tiles, decorations and the flag into the `SpriteBatch`
+pub struct MapRenderSystem;
+
+impl MapRenderSystem {
+    fn render_tile(&self, world: &World, t: &Tile, c: Context) {
+        let sprite_sheet = world.resource::<Handle<SpriteSheet>>();
+        let sprite_sheet = sprite_sheet.get();
+        let height = world.resource::<ScreenSize>().height;
+        let sprite = match t.sprite_id(&sprite_sheet) {
+            Some(sprite) => sprite,
+            None => return,
+        };
+        let sprite_size = sprite_sheet.size(sprite);
+        let mut batch = world.resource_mut::<SpriteBatch>();
+        sprite_sheet.queue(
+            &mut batch,
+            sprite,
+            t.coords(height as f64, 64.0, sprite_size),
+            c,
+        );
+    }
+}
+
+impl RenderSystem for MapRenderSystem {
+    fn render(&self, world: &World, c: Context, g: &mut dyn Canvas, _alpha: f64) {
+        g.clear(BACKGROUND);
+
+        let map = world.resource::<Map>();
+        map.tiles.iter().for_each(|t| self.render_tile(world, t, c));
+        map.decorations
+            .iter()
+            .for_each(|t| self.render_tile(world, t, c));
+        self.render_tile(world, &map.flag, c);
+    }
+}
+
+pub struct PlayerRenderSystem;
+
+impl RenderSystem for PlayerRenderSystem {
+    fn render(&self, world: &World, c: Context, _g: &mut dyn Canvas, alpha: f64) {
+        let sprite_sheet = world.resource::<Handle<SpriteSheet>>();
+        let sprite_sheet = sprite_sheet.get();
+        let mut batch = world.resource_mut::<SpriteBatch>();
+        let height = world.resource::<ScreenSize>().height;
+        for (_, player) in world.read::<Player>().iter() {
+            let player_args = player.render_args(alpha);
+            let sprite = match player_args.sprite {
+                Some(sprite) => sprite,
+                None => continue,
+            };
+            let player_size = sprite_sheet.size(sprite);
+            let params = DrawParams {
+                flip_x: player_args.flip,
+                ..DrawParams::default()
+            };
+            sprite_sheet.queue_with(
+                &mut batch,
+                sprite,
+                [
+                    player_args.pos[0] - player_size[0] / 2.0,
+                    height as f64 - player_args.pos[1] - player_args.size[1],
+                ],
+                &params,
+                c,
+            );
+        }
+    }
+}
+
+// Draws everything the other render systems queued in one go, add it after them
+pub struct SpriteBatchRenderSystem;
+
+impl RenderSystem for SpriteBatchRenderSystem {
+    fn render(&self, world: &World, _c: Context, g: &mut dyn Canvas, _alpha: f64) {
+        let sprite_sheet = world.resource::<Handle<SpriteSheet>>();
+        let mut batch = world.resource_mut::<SpriteBatch>();
+        sprite_sheet.get().draw_batch(&mut batch, g);
+    }
+}
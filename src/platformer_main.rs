@@ -3,80 +3,322 @@ extern crate piston_window;
 extern crate quick_xml;
 extern crate rand;
 extern crate serde;
+extern crate serde_json;
+extern crate toml;
 
 mod platformer;
 mod utils;
 
 use piston_window::*;
+use platformer::actions::{default_bindings, Action, BINDINGS_FILE};
+use platformer::constants::TILE_SIZE;
 use platformer::game_world::{GameWorld, GameWorldUpdateArgs};
-use std::time::SystemTime;
+use platformer::map::Map;
+use platformer::pause_screen::PauseScreen;
+use std::env;
+use std::path::{Path, PathBuf};
+use utils::action_map::ActionMap;
+use utils::asset_error::{or_exit, AssetError};
+use utils::assets::{Assets, Handle};
+use utils::canvas::Canvas;
+use utils::config::{GameConfig, WindowConfig};
+use utils::debug_overlay::DebugInfo;
 use utils::entity::*;
-use utils::game_window::GameWindow;
+use utils::game_window::{GameWindow, ASSETS_FOLDER};
+use utils::headless::{headless_ticks, is_headless, run_headless};
 use utils::input::InputHandler;
+use utils::replay::{run_replay_headless, InputRecorder};
 use utils::scene::Scene;
-use utils::sprite_sheet::SpriteSheet;
+use utils::scene_manager::{SceneManager, SceneTransition};
 
 const WIDTH: u32 = 640;
 const HEIGHT: u32 = 448;
 
-#[derive(Clone)]
 pub struct PlatformerApp {
     world: GameWorld,
-    last_update: SystemTime,
+    transition: Option<SceneTransition>,
 }
 
 impl PlatformerApp {
-    fn new(width: u32, height: u32, sprite_sheet: SpriteSheet) -> PlatformerApp {
-        PlatformerApp {
-            world: GameWorld::new(width, height, sprite_sheet),
-            last_update: SystemTime::now(),
-        }
+    fn new(config: &GameConfig, assets: &mut Assets) -> Result<PlatformerApp, AssetError> {
+        // `--level <path>` loads the map from a level file instead of the built-in one
+        let map = match config.level {
+            Some(ref path) => assets.load::<Map, _>(level_path(path)?)?,
+            None => Handle::new(Map::new(TILE_SIZE)),
+        };
+        Ok(PlatformerApp {
+            world: GameWorld::new(
+                config.window.width,
+                config.window.height,
+                // assets from Kenney, find out more at https://kenney.nl/
+                assets.sprite_sheet("sprites.xml")?,
+                map,
+                load_bindings(),
+            )?,
+            transition: None,
+        })
     }
 }
 
 impl Scene for PlatformerApp {
-    fn render(self, c: Context, g: &mut G2d) {
-        self.world.render(c, g);
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        self.world.render(c, g, alpha);
     }
 
-    fn update(&mut self) {
-        let dt = SystemTime::now()
-            .duration_since(self.last_update)
-            .unwrap()
-            .as_secs_f64();
-        self.world.update(GameWorldUpdateArgs { dt });
-        self.last_update = SystemTime::now();
+    fn update(&mut self, dt: f64) {
+        self.world.update(&GameWorldUpdateArgs { dt });
     }
 
     fn on_resize(&mut self, new_width: u32, new_height: u32) {
-        self.world.width = new_width;
-        self.world.height = new_height;
+        self.world.resize(new_width, new_height);
+    }
+
+    fn transition(&mut self) -> Option<SceneTransition> {
+        self.transition.take()
+    }
+
+    fn debug_info(&self, info: &mut DebugInfo) {
+        self.world.debug_info(info);
     }
 }
 
 impl InputHandler for PlatformerApp {
     fn on_button_event(&mut self, args: ButtonArgs) {
-        self.world.input.on_key_event(args);
+        if self.world.is_bound(Action::Pause, args.button) && args.state == ButtonState::Press {
+            // keys released while paused would otherwise stay pressed after resuming
+            self.world.reset_input();
+            self.transition = Some(SceneTransition::push(PauseScreen::new(
+                self.world.bindings(),
+            )));
+            return;
+        }
+        self.world.on_button_event(args);
+    }
+
+    fn on_move_event(&mut self, motion: Motion) {
+        self.world.on_move_event(motion);
     }
+
+    fn end_tick(&mut self, dt: f64) {
+        self.world.end_tick(dt);
+    }
+}
+
+// The bindings can be changed in `bindings.toml`, which is created with the defaults on the
+// first run. If it can't be read the defaults are used, without overwriting it.
+fn load_bindings() -> ActionMap<Action> {
+    if Path::new(BINDINGS_FILE).exists() {
+        return ActionMap::load(BINDINGS_FILE).unwrap_or_else(|e| {
+            println!("{}, using the default bindings", e);
+            default_bindings()
+        });
+    }
+    let bindings = default_bindings();
+    if let Err(e) = bindings.save(BINDINGS_FILE) {
+        println!("Cannot save bindings to {}: {}", BINDINGS_FILE, e);
+    }
+    bindings
+}
+
+// Levels given on the command line are relative to the working directory rather than to the
+// assets folder
+fn level_path(path: &str) -> Result<PathBuf, AssetError> {
+    env::current_dir()
+        .map(|dir| dir.join(path))
+        .map_err(|e| AssetError::not_found(Path::new(path), e))
 }
 
 fn main() {
-    let mut window: PistonWindow = WindowSettings::new("platformer", [WIDTH, HEIGHT])
-        .graphics_api(OpenGL::V3_2)
-        .exit_on_esc(true)
-        .resizable(false)
-        .build()
-        .unwrap();
-
-    // assets from Kenney, find out more at https://kenney.nl/
-    let sheet = SpriteSheet::new(
-        "assets",
-        "sprites.xml",
-        &mut window.create_texture_context(),
-    );
-
-    let app = PlatformerApp::new(WIDTH, HEIGHT, sheet);
-    let mut game_window = GameWindow::new(window, app);
+    let config =
+        GameConfig::load(WindowConfig::new("platformer", WIDTH, HEIGHT).set_resizable(false));
+
+    let width = config.window.width;
+    let height = config.window.height;
+
+    let mut assets = or_exit(Assets::new(ASSETS_FOLDER));
+    assets.set_strict(config.strict_assets);
+
+    // `--record <file>` / `--replay <file>` record the input of the game or play it back
+    if is_headless() {
+        let app = SceneManager::new(or_exit(PlatformerApp::new(&config, &mut assets)));
+        let app = InputRecorder::new(app, &config);
+        if app.is_replaying() {
+            run_replay_headless(app, width, height);
+        }
+        run_headless(app, width, height, headless_ticks().unwrap_or(0));
+        return;
+    }
+
+    let mut window: PistonWindow = config.window_settings().build().unwrap();
+    assets.set_texture_context(window.create_texture_context());
+
+    let app = SceneManager::new(or_exit(PlatformerApp::new(&config, &mut assets)));
+    let mut game_window = or_exit(GameWindow::with_assets(
+        window,
+        InputRecorder::new(app, &config),
+        &mut assets,
+    ));
+    game_window.debug_overlay().configure(&config.debug);
+    if config.hot_reload {
+        game_window.set_hot_reload(assets);
+    }
 
     game_window.game_loop();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::canvas::DrawCommand;
+    use utils::headless::HeadlessRunner;
+    use utils::replay::replay_headless;
+
+    fn app() -> SceneManager {
+        let mut assets = Assets::new(ASSETS_FOLDER).unwrap();
+        // the default bindings, so the tests don't read or write `bindings.toml`
+        let app = PlatformerApp {
+            world: GameWorld::new(
+                WIDTH,
+                HEIGHT,
+                assets.sprite_sheet("sprites.xml").unwrap(),
+                Handle::new(Map::new(TILE_SIZE)),
+                default_bindings(),
+            )
+            .unwrap(),
+            transition: None,
+        };
+        SceneManager::new(app)
+    }
+
+    fn runner() -> HeadlessRunner<SceneManager> {
+        HeadlessRunner::new(app(), WIDTH, HEIGHT)
+    }
+
+    // The player is queued after the map, so it's the last quad of the sprite batch. The
+    // position is where the image's corner ends up on the screen, in pixels.
+    fn player_pos(runner: &mut HeadlessRunner<SceneManager>) -> [f64; 2] {
+        let quad = runner
+            .render(1.0)
+            .iter()
+            .rev()
+            .find_map(|command| match command {
+                DrawCommand::Quads { quads } => quads.last().cloned(),
+                _ => None,
+            })
+            .expect("the player wasn't drawn");
+        let t = quad.transform;
+        [
+            (t[0][2] + 1.0) * WIDTH as f64 / 2.0,
+            (1.0 - t[1][2]) * HEIGHT as f64 / 2.0,
+        ]
+    }
+
+    #[test]
+    fn idle_player_stays_put() {
+        let mut runner = runner();
+        let start = player_pos(&mut runner);
+
+        runner.run(120);
+        assert_eq!(player_pos(&mut runner), start);
+    }
+
+    #[test]
+    fn player_walks_right() {
+        let mut runner = runner();
+        runner.run(60);
+        let start = player_pos(&mut runner);
+
+        runner.press_key(Key::D).run(30).release_key(Key::D);
+        let moved = player_pos(&mut runner);
+        assert!(moved[0] > start[0], "{:?} -> {:?}", start, moved);
+    }
+
+    #[test]
+    fn player_jumps_and_comes_down() {
+        let mut runner = runner();
+        runner.run(60);
+        let ground = player_pos(&mut runner);
+
+        runner.press_key(Key::Space).run(10).release_key(Key::Space);
+        let in_air = player_pos(&mut runner);
+        assert!(in_air[1] < ground[1], "{:?} -> {:?}", ground, in_air);
+
+        runner.run(120);
+        assert_eq!(player_pos(&mut runner), ground);
+    }
+
+    #[test]
+    fn pause_freezes_the_player() {
+        let mut runner = runner();
+        runner.run(60);
+        runner.press_key(Key::P).release_key(Key::P).run(1);
+        let paused = player_pos(&mut runner);
+
+        runner.press_key(Key::D).run(30);
+        assert_eq!(player_pos(&mut runner), paused);
+
+        // the keys held while paused are dropped when resuming
+        runner
+            .release_key(Key::D)
+            .press_key(Key::P)
+            .release_key(Key::P);
+        runner.press_key(Key::D).run(30);
+        assert!(player_pos(&mut runner)[0] > paused[0]);
+    }
+
+    // Recorded with real input, the replay has to end up in the recorded state
+    #[test]
+    fn replays_the_recording() {
+        let mut config = GameConfig::new(&WindowConfig::new("platformer", WIDTH, HEIGHT));
+        config.set_replay("tests/replays/platformer.json");
+        let recorder = InputRecorder::new(app(), &config);
+        assert!(recorder.is_replaying());
+
+        let runner = replay_headless(recorder, config.window.width, config.window.height);
+        assert!(runner.ticks() > 0);
+        assert_eq!(runner.scene().matches_recording(), Some(true));
+    }
+
+    // Moves the standing player with the left stick for a quarter of a second, starting
+    // between the left edge of the screen and the first wall
+    fn stick_distance(position: f64) -> f64 {
+        let mut runner = runner();
+        runner.press_key(Key::D).run(10).release_key(Key::D).run(60);
+        let start = player_pos(&mut runner);
+        runner.controller_axis(0, 0, position).run(15);
+        player_pos(&mut runner)[0] - start[0]
+    }
+
+    #[test]
+    fn player_runs_with_the_stick() {
+        assert!(stick_distance(1.0) > 0.0);
+        assert!(stick_distance(-1.0) < 0.0);
+        assert!(stick_distance(-0.6) < 0.0);
+    }
+
+    #[test]
+    fn player_ignores_the_stick_inside_the_dead_zone() {
+        assert_eq!(stick_distance(0.15), 0.0);
+        assert_eq!(stick_distance(-0.2), 0.0);
+    }
+
+    #[test]
+    fn player_walks_and_jumps_with_gamepad_buttons() {
+        let mut runner = runner();
+        let start = player_pos(&mut runner);
+
+        // d-pad right, then A
+        runner
+            .press_controller(0, 14)
+            .run(30)
+            .release_controller(0, 14);
+        let moved = player_pos(&mut runner);
+        assert!(moved[0] > start[0]);
+
+        runner
+            .press_controller(0, 0)
+            .run(10)
+            .release_controller(0, 0);
+        assert!(player_pos(&mut runner)[1] < moved[1]);
+    }
+}
@@ -3,6 +3,8 @@ extern crate piston_window;
 extern crate quick_xml;
 extern crate rand;
 extern crate serde;
+extern crate serde_json;
+extern crate toml;
 
 mod geometry;
 mod utils;
@@ -11,67 +13,79 @@ use geometry::ball::{Ball, BallSettings, BallUpdateArgs};
 use geometry::vector2d::Vector2d;
 use geometry::velocity::Velocity;
 use piston_window::*;
-use std::time::SystemTime;
-use utils::entity::{Renderable, Updatable};
+use utils::canvas::Canvas;
+use utils::config::{GameConfig, WindowConfig};
+use utils::debug_overlay::DebugInfo;
+use utils::ecs::{DeltaTime, RenderableSystem, Schedule, ScreenSize, UpdatableSystem, World};
 use utils::game_window::GameWindow;
+use utils::headless::{headless_ticks, is_headless, run_headless};
 use utils::input::InputHandler;
+use utils::replay::{run_replay_headless, InputRecorder};
 use utils::scene::Scene;
 
 const WIDTH: u32 = 1280;
 const HEIGHT: u32 = 720;
 const CORNFLOWER_BLUE: [f32; 4] = [0.392, 0.584, 0.929, 1.0];
 
-#[derive(Clone)]
 pub struct SimpleBalls {
-    balls: Vec<Ball>,
-    width: u32,
-    height: u32,
-    last_update: SystemTime,
+    world: World,
+    schedule: Schedule,
 }
 
 impl SimpleBalls {
-    pub fn new(width: u32, height: u32) -> SimpleBalls {
-        SimpleBalls {
-            balls: (0..100)
-                .map(|id| {
-                    BallSettings::new()
-                        .set_max_pos(WIDTH as f64, HEIGHT as f64)
-                        .build(id)
-                })
-                .collect(),
-            width,
-            height,
-            last_update: SystemTime::now(),
+    pub fn new(config: &GameConfig) -> SimpleBalls {
+        let width = config.window.width;
+        let height = config.window.height;
+        let mut world = World::new();
+        world.insert_resource(DeltaTime(0.0));
+        world.insert_resource(ScreenSize { width, height });
+        world.register::<Ball>();
+        let mut rng = config.rng();
+        let mut settings = BallSettings::from_config(&config.balls);
+        settings.set_max_pos(width as f64, height as f64);
+        for id in 0..config.balls.count {
+            let ball = world.spawn();
+            world.insert(ball, settings.build_with_rng(id, &mut rng));
         }
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(UpdatableSystem::<Ball, _>::new(|world: &World| {
+                let screen = *world.resource::<ScreenSize>();
+                BallUpdateArgs {
+                    dt: world.resource::<DeltaTime>().0,
+                    width: screen.width,
+                    height: screen.height,
+                    balls: None,
+                    events: None,
+                }
+            }))
+            .add_render_system(RenderableSystem::<Ball>::new());
+
+        SimpleBalls { world, schedule }
     }
 }
 
 impl Scene for SimpleBalls {
-    fn render(self, c: Context, g: &mut G2d) {
-        clear(CORNFLOWER_BLUE, g);
-        self.balls.iter().for_each(|b| b.render(c, g));
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        g.clear(CORNFLOWER_BLUE);
+        self.schedule.render(&self.world, c, g, alpha);
     }
 
-    fn update(&mut self) {
-        let duration = SystemTime::now().duration_since(self.last_update).unwrap();
-        let w = self.width;
-        let h = self.height;
-        let dt = duration.as_secs_f64();
-        let update_args = BallUpdateArgs {
-            dt,
-            width: w,
-            height: h,
-            balls: None,
-        };
-        self.balls
-            .iter_mut()
-            .for_each(|b| b.update(update_args.clone()));
-        self.last_update = SystemTime::now();
+    fn update(&mut self, dt: f64) {
+        *self.world.resource_mut::<DeltaTime>() = DeltaTime(dt);
+        self.schedule.run(&self.world);
     }
 
     fn on_resize(&mut self, new_width: u32, new_height: u32) {
-        self.width = new_width;
-        self.height = new_height;
+        *self.world.resource_mut::<ScreenSize>() = ScreenSize {
+            width: new_width,
+            height: new_height,
+        };
+    }
+
+    fn debug_info(&self, info: &mut DebugInfo) {
+        info.add("balls", self.world.read::<Ball>().len());
     }
 }
 
@@ -80,14 +94,76 @@ impl InputHandler for SimpleBalls {
 }
 
 fn main() {
-    let window = WindowSettings::new("simple-balls", [WIDTH, HEIGHT])
-        .graphics_api(OpenGL::V3_2)
-        .exit_on_esc(true)
-        .build()
-        .unwrap();
+    let config = GameConfig::load(&WindowConfig::new("simple-balls", WIDTH, HEIGHT));
+    let width = config.window.width;
+    let height = config.window.height;
 
-    let app = SimpleBalls::new(WIDTH, HEIGHT);
+    // `--record <file>` / `--replay <file>` record the input of the game or play it back
+    let app = InputRecorder::new(SimpleBalls::new(&config), &config);
+
+    if is_headless() {
+        if app.is_replaying() {
+            run_replay_headless(app, width, height);
+        }
+        run_headless(app, width, height, headless_ticks().unwrap_or(0));
+        return;
+    }
+
+    let window = config.window_settings().build().unwrap();
 
     let mut game_window = GameWindow::new(window, app);
+    game_window.debug_overlay().configure(&config.debug);
     game_window.game_loop();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::canvas::DrawCommand;
+    use utils::headless::HeadlessRunner;
+
+    fn runner(count: u32) -> HeadlessRunner<SimpleBalls> {
+        let mut config = GameConfig::new(&WindowConfig::new("simple-balls", WIDTH, HEIGHT));
+        config.balls.count = count;
+        config.seed = Some(42);
+        HeadlessRunner::new(SimpleBalls::new(&config), WIDTH, HEIGHT)
+    }
+
+    // The rects of the balls drawn in the frame
+    fn ball_rects(commands: &[DrawCommand]) -> Vec<[f64; 4]> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                DrawCommand::Ellipse { rect, .. } => Some(*rect),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn is_on_screen(rect: &[f64; 4]) -> bool {
+        let center = [rect[0] + rect[2] / 2.0, rect[1] + rect[3] / 2.0];
+        (0.0..=WIDTH as f64).contains(&center[0]) && (0.0..=HEIGHT as f64).contains(&center[1])
+    }
+
+    #[test]
+    fn draws_every_ball() {
+        let mut runner = runner(25);
+        assert_eq!(ball_rects(runner.render(1.0)).len(), 25);
+
+        runner.run(120);
+        assert_eq!(ball_rects(runner.render(1.0)).len(), 25);
+    }
+
+    #[test]
+    fn balls_move_and_stay_on_screen() {
+        let mut runner = runner(25);
+        let start = ball_rects(runner.render(1.0));
+
+        for _ in 0..10 {
+            runner.run(30);
+            let rects = ball_rects(runner.render(1.0));
+            assert!(rects.iter().all(is_on_screen), "{:?}", rects);
+        }
+        assert_ne!(ball_rects(runner.render(1.0)), start);
+    }
+}
@@ -3,15 +3,23 @@ extern crate piston_window;
 extern crate quick_xml;
 extern crate rand;
 extern crate serde;
+extern crate serde_json;
+extern crate toml;
 
 mod utils;
 
+use piston::Button::Keyboard;
 use piston_window::*;
-use std::time::SystemTime;
-use utils::game_window::GameWindow;
+use utils::asset_error::or_exit;
+use utils::assets::{Assets, Handle};
+use utils::canvas::Canvas;
+use utils::clock::{RealClock, ScaledClock, TimeScale};
+use utils::config::{GameConfig, WindowConfig};
+use utils::debug_overlay::DebugInfo;
+use utils::game_window::{GameWindow, ASSETS_FOLDER};
 use utils::input::InputHandler;
 use utils::scene::Scene;
-use utils::sprite_sheet::SpriteSheet;
+use utils::sprite_sheet::{SpriteId, SpriteSheet};
 
 const WIDTH: u32 = 192;
 const HEIGHT: u32 = 192;
@@ -25,73 +33,80 @@ const PLAYER_WALK_FRAMES: [&str; 4] = [
     "playerRed_walk2.png",
 ];
 const SCREEN_MOVE_PER_SECOND: f64 = 192.0;
+const MAX_TIME_SCALE: f64 = 8.0;
 
-#[derive(Clone)]
 pub struct SpriteAnimationApp {
     width: u32,
     height: u32,
-    sprite_sheet: SpriteSheet,
+    sprite_sheet: Handle<SpriteSheet>,
+    tile: SpriteId,
+    plant: SpriteId,
+    player_frames: Vec<SpriteId>,
     player_size: [f64; 2],
     player_frame: u32,
-    last_update: SystemTime,
-    last_frame: SystemTime,
+    frame_time: f64,
+    time_scale: TimeScale,
     plant_x: f64,
 }
 
 impl SpriteAnimationApp {
-    pub fn new(width: u32, height: u32, sprite_sheet: SpriteSheet) -> SpriteAnimationApp {
+    pub fn new(
+        width: u32,
+        height: u32,
+        sprite_sheet: Handle<SpriteSheet>,
+        time_scale: TimeScale,
+    ) -> SpriteAnimationApp {
+        let sheet = sprite_sheet.get();
+        let sprite = |name: &str| {
+            sheet
+                .sprite_id(name)
+                .unwrap_or_else(|| panic!("Unknown sprite {}", name))
+        };
+        let tile = sprite("tileYellow_06.png");
+        let plant = sprite("plantGreen_3.png");
+        let player_frames = PLAYER_WALK_FRAMES.iter().map(|name| sprite(name)).collect();
+        drop(sheet);
+
         SpriteAnimationApp {
             width,
             height,
             sprite_sheet,
+            tile,
+            plant,
+            player_frames,
             player_size: [39.0, 48.0],
             player_frame: 0,
-            last_update: SystemTime::now(),
-            last_frame: SystemTime::now(),
+            frame_time: 0.0,
+            time_scale,
             plant_x: 106.0,
         }
     }
 
-    fn update_player(&mut self) {
-        let dt = SystemTime::now()
-            .duration_since(self.last_frame)
-            .unwrap()
-            .as_secs_f64();
-        if dt >= 1.0 / PLAYER_WALK_FPS as f64 {
+    fn update_player(&mut self, dt: f64) {
+        self.frame_time += dt;
+        if self.frame_time >= 1.0 / PLAYER_WALK_FPS as f64 {
             self.player_frame = (self.player_frame + 1) % PLAYER_WALK_FRAMES.len() as u32;
-            self.last_frame = SystemTime::now();
+            self.frame_time = 0.0;
         }
     }
 }
 
 impl Scene for SpriteAnimationApp {
-    fn render(self, c: Context, g: &mut G2d) {
+    fn render(&self, c: Context, g: &mut dyn Canvas, _alpha: f64) {
         let w = self.width as f64;
         let h = self.height as f64;
 
-        clear(BACKGROUND, g);
-        self.sprite_sheet
-            .render_sprite(&"tileYellow_06.png".to_string(), [0.0, h - 64.0], c, g);
-        self.sprite_sheet.render_sprite(
-            &"plantGreen_3.png".to_string(),
-            [self.plant_x, h - 95.0],
-            c,
-            g,
-        );
+        g.clear(BACKGROUND);
+        let sheet = self.sprite_sheet.get();
+        sheet.render(self.tile, [0.0, h - 64.0], c, g);
+        sheet.render(self.plant, [self.plant_x, h - 95.0], c, g);
         if self.plant_x < 0.0 {
-            self.sprite_sheet.render_sprite(
-                &"plantGreen_3.png".to_string(),
-                [w + self.plant_x, h - 95.0],
-                c,
-                g,
-            );
+            sheet.render(self.plant, [w + self.plant_x, h - 95.0], c, g);
         }
-        self.sprite_sheet
-            .render_sprite(&"tileYellow_06.png".to_string(), [64.0, h - 64.0], c, g);
-        self.sprite_sheet
-            .render_sprite(&"tileYellow_06.png".to_string(), [128.0, h - 64.0], c, g);
-        self.sprite_sheet.render_sprite(
-            &PLAYER_WALK_FRAMES[self.player_frame as usize].to_string(),
+        sheet.render(self.tile, [64.0, h - 64.0], c, g);
+        sheet.render(self.tile, [128.0, h - 64.0], c, g);
+        sheet.render(
+            self.player_frames[self.player_frame as usize],
             [
                 64.0 + (64.0 - self.player_size[0]) / 2.0,
                 h - 64.0 - self.player_size[1],
@@ -101,48 +116,64 @@ impl Scene for SpriteAnimationApp {
         );
     }
 
-    fn update(&mut self) {
-        let dt = SystemTime::now()
-            .duration_since(self.last_update)
-            .unwrap()
-            .as_secs_f64();
-
-        self.update_player();
+    fn update(&mut self, dt: f64) {
+        self.update_player(dt);
         self.plant_x -= SCREEN_MOVE_PER_SECOND * dt;
         if self.plant_x <= -44.0 {
             self.plant_x = self.width as f64 - 44.0;
         }
-
-        self.last_update = SystemTime::now();
     }
 
     fn on_resize(&mut self, new_width: u32, new_height: u32) {
         self.width = new_width;
         self.height = new_height;
     }
+
+    fn debug_info(&self, info: &mut DebugInfo) {
+        info.add("time scale", self.time_scale.get());
+    }
 }
 
 impl InputHandler for SpriteAnimationApp {
-    fn on_button_event(&mut self, _args: ButtonArgs) {}
+    fn on_button_event(&mut self, args: ButtonArgs) {
+        if args.state != ButtonState::Press {
+            return;
+        }
+        let scale = self.time_scale.get();
+        match args.button {
+            Keyboard(Key::Up) => self.time_scale.set((scale * 2.0).min(MAX_TIME_SCALE)),
+            Keyboard(Key::Down) => self.time_scale.set(scale / 2.0),
+            Keyboard(Key::Space) => self.time_scale.set(if scale == 0.0 { 1.0 } else { 0.0 }),
+            _ => {}
+        }
+    }
 }
 
 fn main() {
-    let mut window: PistonWindow = WindowSettings::new("sprite-sheet", [WIDTH, HEIGHT])
-        .graphics_api(OpenGL::V3_2)
-        .exit_on_esc(true)
-        .resizable(false)
-        .build()
-        .unwrap();
+    let config =
+        GameConfig::load(WindowConfig::new("sprite-sheet", WIDTH, HEIGHT).set_resizable(false));
+    let mut window: PistonWindow = config.window_settings().build().unwrap();
+
+    let mut assets = or_exit(Assets::new(ASSETS_FOLDER));
+    assets.set_texture_context(window.create_texture_context());
 
     // assets from Kenney, find out more at https://kenney.nl/
-    let sheet = SpriteSheet::new(
-        "assets",
-        "sprites.xml",
-        &mut window.create_texture_context(),
+    let sheet = or_exit(assets.sprite_sheet("sprites.xml"));
+
+    // Up / Down speeds up / slows down the animation, Space pauses it
+    let clock = ScaledClock::new(RealClock::new());
+    let app = SpriteAnimationApp::new(
+        config.window.width,
+        config.window.height,
+        sheet,
+        clock.time_scale(),
     );
-
-    let app = SpriteAnimationApp::new(WIDTH, HEIGHT, sheet);
-    let mut game_window = GameWindow::new(window, app);
+    let mut game_window = or_exit(GameWindow::with_assets(window, app, &mut assets));
+    game_window.debug_overlay().configure(&config.debug);
+    game_window.set_clock(clock);
+    if config.hot_reload {
+        game_window.set_hot_reload(assets);
+    }
 
     game_window.game_loop();
 }
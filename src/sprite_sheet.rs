@@ -3,15 +3,25 @@ extern crate piston_window;
 extern crate quick_xml;
 extern crate rand;
 extern crate serde;
+extern crate serde_json;
+extern crate toml;
 
 mod utils;
 
-use piston::WindowSettings;
 use piston_window::*;
-use utils::game_window::GameWindow;
+use std::cell::RefCell;
+use std::time::Instant;
+use utils::asset_error::or_exit;
+use utils::assets::{Assets, Handle};
+use utils::canvas::Canvas;
+use utils::config::{GameConfig, WindowConfig};
+use utils::debug_overlay::DebugInfo;
+use utils::game_window::{GameWindow, ASSETS_FOLDER};
+use utils::headless::{headless_ticks, is_headless, HeadlessRunner};
 use utils::input::InputHandler;
 use utils::scene::Scene;
-use utils::sprite_sheet::SpriteSheet;
+use utils::sprite_batch::SpriteBatch;
+use utils::sprite_sheet::{SpriteId, SpriteSheet};
 
 const WIDTH: u32 = 640;
 const HEIGHT: u32 = 384;
@@ -19,15 +29,17 @@ const CORNFLOWER_BLUE: [f32; 4] = [0.392, 0.584, 0.929, 1.0];
 
 #[derive(Clone)]
 pub struct Tile {
-    sprite_name: String,
+    sprite: SpriteId,
     x: u32,
     y: u32,
 }
 
 impl Tile {
-    pub fn new(sprite_name: &str, x: u32, y: u32) -> Tile {
+    pub fn new(sprite_sheet: &SpriteSheet, sprite_name: &str, x: u32, y: u32) -> Tile {
         Tile {
-            sprite_name: sprite_name.to_string(),
+            sprite: sprite_sheet
+                .sprite_id(sprite_name)
+                .unwrap_or_else(|| panic!("Unknown sprite {}", sprite_name)),
             x,
             y,
         }
@@ -41,91 +53,205 @@ impl Tile {
     }
 }
 
-#[derive(Clone)]
 pub struct SpriteSheetApp {
     width: u32,
     height: u32,
-    sprite_sheet: SpriteSheet,
+    sprite_sheet: Handle<SpriteSheet>,
     tiles: Vec<Tile>,
+    batched: bool,
+    // Reused every frame, so its buffer is only allocated once
+    batch: RefCell<SpriteBatch>,
 }
 
 impl SpriteSheetApp {
-    pub fn new(width: u32, height: u32, sprite_sheet: SpriteSheet) -> SpriteSheetApp {
+    pub fn new(width: u32, height: u32, sprite_sheet: Handle<SpriteSheet>) -> SpriteSheetApp {
+        let sheet = sprite_sheet.get();
+        let tiles = vec![
+            Tile::new(&sheet, "tileYellow_04.png", 0, 0),
+            Tile::new(&sheet, "tileYellow_04.png", 0, 1),
+            Tile::new(&sheet, "tileYellow_04.png", 0, 2),
+            Tile::new(&sheet, "tileYellow_06.png", 0, 3),
+            Tile::new(&sheet, "tileYellow_04.png", 1, 0),
+            Tile::new(&sheet, "tileYellow_09.png", 1, 1),
+            Tile::new(&sheet, "tileYellow_20.png", 1, 2),
+            Tile::new(&sheet, "tileYellow_11.png", 1, 3),
+            Tile::new(&sheet, "tileYellow_04.png", 2, 0),
+            Tile::new(&sheet, "tileYellow_20.png", 2, 1),
+            Tile::new(&sheet, "tileYellow_11.png", 2, 2),
+            Tile::new(&sheet, "tileYellow_04.png", 3, 0),
+            Tile::new(&sheet, "tileYellow_06.png", 3, 1),
+            Tile::new(&sheet, "tileYellow_18.png", 4, 0),
+            Tile::new(&sheet, "tileYellow_06.png", 4, 1),
+            Tile::new(&sheet, "tileYellow_04.png", 5, 0),
+            Tile::new(&sheet, "tileYellow_07.png", 5, 1),
+            Tile::new(&sheet, "tileYellow_06.png", 6, 0),
+            Tile::new(&sheet, "tileYellow_06.png", 7, 0),
+            Tile::new(&sheet, "tileYellow_04.png", 8, 0),
+            Tile::new(&sheet, "tileYellow_04.png", 8, 1),
+            Tile::new(&sheet, "tileYellow_05.png", 8, 2),
+            Tile::new(&sheet, "tileYellow_04.png", 9, 0),
+            Tile::new(&sheet, "tileYellow_09.png", 9, 1),
+            Tile::new(&sheet, "tileYellow_06.png", 9, 2),
+        ];
+        drop(sheet);
+
         SpriteSheetApp {
             width,
             height,
             sprite_sheet,
-            tiles: vec![
-                Tile::new("tileYellow_04.png", 0, 0),
-                Tile::new("tileYellow_04.png", 0, 1),
-                Tile::new("tileYellow_04.png", 0, 2),
-                Tile::new("tileYellow_06.png", 0, 3),
-                Tile::new("tileYellow_04.png", 1, 0),
-                Tile::new("tileYellow_09.png", 1, 1),
-                Tile::new("tileYellow_20.png", 1, 2),
-                Tile::new("tileYellow_11.png", 1, 3),
-                Tile::new("tileYellow_04.png", 2, 0),
-                Tile::new("tileYellow_20.png", 2, 1),
-                Tile::new("tileYellow_11.png", 2, 2),
-                Tile::new("tileYellow_04.png", 3, 0),
-                Tile::new("tileYellow_06.png", 3, 1),
-                Tile::new("tileYellow_18.png", 4, 0),
-                Tile::new("tileYellow_06.png", 4, 1),
-                Tile::new("tileYellow_04.png", 5, 0),
-                Tile::new("tileYellow_07.png", 5, 1),
-                Tile::new("tileYellow_06.png", 6, 0),
-                Tile::new("tileYellow_06.png", 7, 0),
-                Tile::new("tileYellow_04.png", 8, 0),
-                Tile::new("tileYellow_04.png", 8, 1),
-                Tile::new("tileYellow_05.png", 8, 2),
-                Tile::new("tileYellow_04.png", 9, 0),
-                Tile::new("tileYellow_09.png", 9, 1),
-                Tile::new("tileYellow_06.png", 9, 2),
-            ],
+            tiles,
+            batched: true,
+            batch: RefCell::new(SpriteBatch::new()),
         }
     }
+
+    // Without batching every tile is a draw call of its own
+    pub fn set_batched(&mut self, batched: bool) -> &mut SpriteSheetApp {
+        self.batched = batched;
+        self
+    }
 }
 
 impl Scene for SpriteSheetApp {
-    fn render(self, c: Context, g: &mut G2d) {
+    fn render(&self, c: Context, g: &mut dyn Canvas, _alpha: f64) {
         let h = self.height as f64;
-        clear(CORNFLOWER_BLUE, g);
+        g.clear(CORNFLOWER_BLUE);
+        let sprite_sheet = self.sprite_sheet.get();
         let tiles = &self.tiles;
+        if !self.batched {
+            tiles.iter().for_each(|t| {
+                sprite_sheet.render(t.sprite, t.coords(h, 64.0), c, g);
+            });
+            return;
+        }
+
+        let mut batch = self.batch.borrow_mut();
         tiles.iter().for_each(|t| {
-            self.sprite_sheet
-                .render_sprite(&t.sprite_name, t.coords(h, 64.0), c, g);
+            sprite_sheet.queue(&mut batch, t.sprite, t.coords(h, 64.0), c);
         });
+        sprite_sheet.draw_batch(&mut batch, g);
     }
 
-    fn update(&mut self) {}
+    fn update(&mut self, _dt: f64) {}
 
     fn on_resize(&mut self, new_width: u32, new_height: u32) {
         self.width = new_width;
         self.height = new_height;
     }
+
+    fn debug_info(&self, info: &mut DebugInfo) {
+        info.add("tiles", self.tiles.len());
+    }
 }
 
 impl InputHandler for SpriteSheetApp {
     fn on_button_event(&mut self, _args: ButtonArgs) {}
 }
 
+// `--headless [frames]` renders the same frame with and without batching into a
+// `RecordingCanvas` and compares how many draw calls it takes and how long
+fn run_benchmark(config: &GameConfig) {
+    let mut assets = or_exit(Assets::new(ASSETS_FOLDER));
+    let sheet = or_exit(assets.sprite_sheet("sprites.xml"));
+    let (width, height) = (config.window.width, config.window.height);
+    let frames = headless_ticks().unwrap_or(1000).max(1);
+
+    let app = SpriteSheetApp::new(width, height, sheet);
+    let mut runner = HeadlessRunner::new(app, width, height);
+    for &batched in &[false, true] {
+        runner.scene_mut().set_batched(batched);
+        let start = Instant::now();
+        let mut draw_calls = 0;
+        for _ in 0..frames {
+            draw_calls = runner.render(0.0).len();
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        println!(
+            "{}: {} draw calls per frame, {:.3} ms per frame over {} frames",
+            if batched { "batched" } else { "one by one" },
+            draw_calls,
+            elapsed * 1000.0 / frames as f64,
+            frames
+        );
+    }
+}
+
 fn main() {
-    let mut window: PistonWindow = WindowSettings::new("sprite-sheet", [WIDTH, HEIGHT])
-        .graphics_api(OpenGL::V3_2)
-        .exit_on_esc(true)
-        .resizable(false)
-        .build()
-        .unwrap();
+    let config =
+        GameConfig::load(WindowConfig::new("sprite-sheet", WIDTH, HEIGHT).set_resizable(false));
+    if is_headless() {
+        run_benchmark(&config);
+        return;
+    }
+
+    let mut window: PistonWindow = config.window_settings().build().unwrap();
+
+    let mut assets = or_exit(Assets::new(ASSETS_FOLDER));
+    assets.set_texture_context(window.create_texture_context());
 
     // assets from Kenney, find out more at https://kenney.nl/
-    let sheet = SpriteSheet::new(
-        "assets",
-        "sprites.xml",
-        &mut window.create_texture_context(),
-    );
+    let sheet = or_exit(assets.sprite_sheet("sprites.xml"));
 
-    let app = SpriteSheetApp::new(WIDTH, HEIGHT, sheet);
-    let mut game_window = GameWindow::new(window, app);
+    let app = SpriteSheetApp::new(config.window.width, config.window.height, sheet);
+    let mut game_window = or_exit(GameWindow::with_assets(window, app, &mut assets));
+    game_window.debug_overlay().configure(&config.debug);
+    if config.hot_reload {
+        game_window.set_hot_reload(assets);
+    }
 
     game_window.game_loop();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::canvas::{DrawCommand, Quad};
+
+    fn runner(batched: bool) -> HeadlessRunner<SpriteSheetApp> {
+        let mut assets = Assets::new(ASSETS_FOLDER).unwrap();
+        let sheet = assets.sprite_sheet("sprites.xml").unwrap();
+        let mut app = SpriteSheetApp::new(WIDTH, HEIGHT, sheet);
+        app.set_batched(batched);
+        HeadlessRunner::new(app, WIDTH, HEIGHT)
+    }
+
+    #[test]
+    fn unbatched_tiles_are_drawn_one_by_one() {
+        let mut runner = runner(false);
+        let commands = runner.render(0.0);
+        assert_eq!(commands.len(), 26);
+        assert!(matches!(commands[0], DrawCommand::Clear { .. }));
+        assert!(commands[1..]
+            .iter()
+            .all(|command| matches!(command, DrawCommand::Image { .. })));
+    }
+
+    #[test]
+    fn batched_tiles_are_drawn_in_one_go() {
+        let images: Vec<Quad> = runner(false)
+            .render(0.0)
+            .iter()
+            .filter_map(|command| match *command {
+                DrawCommand::Image {
+                    color,
+                    src_rect,
+                    transform,
+                } => Some(Quad {
+                    color,
+                    src_rect,
+                    transform,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        let mut runner = runner(true);
+        let commands = runner.render(0.0);
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(commands[0], DrawCommand::Clear { .. }));
+        match commands[1] {
+            DrawCommand::Quads { ref quads } => assert_eq!(*quads, images),
+            ref command => panic!("expected quads, got {:?}", command),
+        }
+    }
+}
@@ -0,0 +1,487 @@
+use piston::Button::{Controller, Keyboard, Mouse};
+use piston::{Button, Key, MouseButton};
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::hash::Hash;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use utils::asset_error::AssetError;
+use utils::input::GameInput;
+
+// A key, mouse button, or gamepad button / axis direction an action can be bound to.
+// Gamepad bindings match every connected controller. In the bindings file they are written as
+// "Key:Space", "Mouse:Left", "Pad:7" and "Axis:0+" / "Axis:0-".
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputBinding {
+    Key(Key),
+    Mouse(MouseButton),
+    GamepadButton(u8),
+    GamepadAxis { axis: u8, positive: bool },
+}
+
+impl InputBinding {
+    // Between 0.0 and 1.0, buttons are either fully pressed or not at all
+    fn value(self, input: &GameInput) -> f64 {
+        let pressed = match self {
+            InputBinding::Key(key) => input.is_key_pressed(key),
+            InputBinding::Mouse(button) => input.is_mouse_pressed(button),
+            InputBinding::GamepadButton(button) => input.is_gamepad_pressed(button),
+            InputBinding::GamepadAxis { axis, positive } => {
+                let value = input.gamepad_axis(axis);
+                return if positive {
+                    value.max(0.0)
+                } else {
+                    (-value).max(0.0)
+                };
+            }
+        };
+        if pressed {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn is_just_pressed(self, input: &GameInput) -> bool {
+        match self {
+            InputBinding::Key(key) => input.is_key_just_pressed(key),
+            InputBinding::Mouse(button) => input.is_mouse_just_pressed(button),
+            InputBinding::GamepadButton(button) => input.is_gamepad_just_pressed(button),
+            InputBinding::GamepadAxis { .. } => false,
+        }
+    }
+
+    fn is_just_released(self, input: &GameInput) -> bool {
+        match self {
+            InputBinding::Key(key) => input.is_key_just_released(key),
+            InputBinding::Mouse(button) => input.is_mouse_just_released(button),
+            InputBinding::GamepadButton(button) => input.is_gamepad_just_released(button),
+            InputBinding::GamepadAxis { .. } => false,
+        }
+    }
+
+    fn matches(self, button: Button) -> bool {
+        match (self, button) {
+            (InputBinding::Key(key), Keyboard(k)) => key == k,
+            (InputBinding::Mouse(b), Mouse(m)) => b == m,
+            (InputBinding::GamepadButton(b), Controller(c)) => b == c.button,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for InputBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InputBinding::Key(key) => write!(f, "Key:{}", variant_name(&key)),
+            InputBinding::Mouse(button) => write!(f, "Mouse:{}", variant_name(&button)),
+            InputBinding::GamepadButton(button) => write!(f, "Pad:{}", button),
+            InputBinding::GamepadAxis { axis, positive } => {
+                write!(f, "Axis:{}{}", axis, if positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+impl FromStr for InputBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InputBinding, String> {
+        let invalid = || format!("unknown input \"{}\"", s);
+        let mut parts = s.splitn(2, ':');
+        let (kind, name) = match (parts.next(), parts.next()) {
+            (Some(kind), Some(name)) => (kind, name),
+            _ => return Err(invalid()),
+        };
+        match kind {
+            "Key" => from_variant_name(name).map(InputBinding::Key),
+            "Mouse" => from_variant_name(name).map(InputBinding::Mouse),
+            "Pad" => name.parse().ok().map(InputBinding::GamepadButton),
+            "Axis" if name.ends_with('+') || name.ends_with('-') => {
+                let positive = name.ends_with('+');
+                name[..name.len() - 1]
+                    .parse()
+                    .ok()
+                    .map(|axis| InputBinding::GamepadAxis { axis, positive })
+            }
+            _ => None,
+        }
+        .ok_or_else(invalid)
+    }
+}
+
+// TOML can't hold enums with data, so bindings are stored as strings
+impl Serialize for InputBinding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for InputBinding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<InputBinding, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// The name serde gives a unit variant, e.g. "Space" for `Key::Space`
+fn variant_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn from_variant_name<T: DeserializeOwned>(name: &str) -> Option<T> {
+    serde_json::from_value(Value::String(name.to_string())).ok()
+}
+
+// Maps game specific actions (e.g. an enum with `Jump`, `Pause`, ...) to inputs, so entities
+// never have to check raw keys and the controls can be rebound
+#[derive(Clone, Debug)]
+pub struct ActionMap<A> {
+    bindings: Vec<(A, Vec<InputBinding>)>,
+}
+
+#[allow(dead_code)]
+impl<A: Copy + Eq + Hash> ActionMap<A> {
+    pub fn new() -> ActionMap<A> {
+        ActionMap {
+            bindings: Vec::new(),
+        }
+    }
+
+    // An action can be bound to any number of inputs, and an input to any number of actions
+    pub fn bind(&mut self, action: A, input: InputBinding) -> &mut ActionMap<A> {
+        match self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, inputs)) => {
+                if !inputs.contains(&input) {
+                    inputs.push(input);
+                }
+            }
+            None => self.bindings.push((action, vec![input])),
+        }
+        self
+    }
+
+    pub fn bind_key(&mut self, action: A, key: Key) -> &mut ActionMap<A> {
+        self.bind(action, InputBinding::Key(key))
+    }
+
+    pub fn bind_gamepad_button(&mut self, action: A, button: u8) -> &mut ActionMap<A> {
+        self.bind(action, InputBinding::GamepadButton(button))
+    }
+
+    // The action's value follows the axis while it's pushed towards the given direction
+    pub fn bind_gamepad_axis(&mut self, action: A, axis: u8, positive: bool) -> &mut ActionMap<A> {
+        self.bind(action, InputBinding::GamepadAxis { axis, positive })
+    }
+
+    pub fn unbind(&mut self, action: A, input: InputBinding) -> &mut ActionMap<A> {
+        if let Some((_, inputs)) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            inputs.retain(|i| *i != input);
+        }
+        self
+    }
+
+    pub fn clear(&mut self, action: A) -> &mut ActionMap<A> {
+        self.bindings.retain(|(a, _)| *a != action);
+        self
+    }
+
+    pub fn inputs(&self, action: A) -> &[InputBinding] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, inputs)| inputs.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn is_bound(&self, action: A, button: Button) -> bool {
+        self.inputs(action)
+            .iter()
+            .any(|input| input.matches(button))
+    }
+
+    // Snapshot of the actions that are active with the current input
+    pub fn state(&self, input: &GameInput) -> ActionState<A> {
+        let mut state = ActionState {
+            values: HashMap::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+        };
+        for (action, inputs) in self.bindings.iter() {
+            let value = inputs
+                .iter()
+                .map(|binding| binding.value(input))
+                .fold(0.0, f64::max);
+            if value > 0.0 {
+                state.values.insert(*action, value);
+            }
+            if inputs.iter().any(|binding| binding.is_just_pressed(input)) {
+                state.just_pressed.insert(*action);
+            }
+            if inputs.iter().any(|binding| binding.is_just_released(input)) {
+                state.just_released.insert(*action);
+            }
+        }
+        state
+    }
+}
+
+#[allow(dead_code)]
+impl<A: Copy + Eq + Hash + Serialize + DeserializeOwned> ActionMap<A> {
+    // Bindings are stored as TOML, one `[[bindings]]` table with an action and its inputs each
+    pub fn load(path: &str) -> Result<ActionMap<A>, AssetError> {
+        let path = Path::new(path);
+        let content = fs::read_to_string(path).map_err(|e| AssetError::not_found(path, e))?;
+        ActionMap::parse(path, &content)
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<ActionMap<A>, AssetError> {
+        let file: BindingsFile<A> = toml::from_str(content).map_err(|e| {
+            let location = e.line_col().map(|(line, column)| (line + 1, column + 1));
+            AssetError::parse(path, location, e)
+        })?;
+
+        let mut map = ActionMap::new();
+        for binding in file.bindings {
+            for input in binding.inputs {
+                map.bind(binding.action, input);
+            }
+            for key in binding.keys {
+                map.bind_key(binding.action, key);
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let file = BindingsFile {
+            bindings: self
+                .bindings
+                .iter()
+                .map(|(action, inputs)| Binding {
+                    action: *action,
+                    inputs: inputs.clone(),
+                    keys: Vec::new(),
+                })
+                .collect(),
+        };
+        let content =
+            toml::to_string_pretty(&file).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(path, content)
+    }
+}
+
+impl<A: Copy + Eq + Hash> Default for ActionMap<A> {
+    fn default() -> ActionMap<A> {
+        ActionMap::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ActionState<A: Eq + Hash> {
+    values: HashMap<A, f64>,
+    just_pressed: HashSet<A>,
+    just_released: HashSet<A>,
+}
+
+#[allow(dead_code)]
+impl<A: Copy + Eq + Hash> ActionState<A> {
+    pub fn is_active(&self, action: A) -> bool {
+        self.values.contains_key(&action)
+    }
+
+    // 1.0 for pressed buttons, the position of the stick for axes, 0.0 if inactive
+    pub fn value(&self, action: A) -> f64 {
+        self.values.get(&action).cloned().unwrap_or(0.0)
+    }
+
+    // Any of the action's buttons was pressed during the last tick
+    pub fn is_just_pressed(&self, action: A) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn is_just_released(&self, action: A) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    // Between -1.0 and 1.0, e.g. -1.0 if only `negative` is pressed and 0.0 if both are
+    pub fn axis(&self, negative: A, positive: A) -> f64 {
+        self.value(positive) - self.value(negative)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BindingsFile<A> {
+    bindings: Vec<Binding<A>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Binding<A> {
+    action: A,
+    #[serde(default)]
+    inputs: Vec<InputBinding>,
+    // Only keys could be bound in older files, they are still read but not written anymore
+    #[serde(default, skip_serializing)]
+    keys: Vec<Key>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piston::{ButtonArgs, ButtonState, ControllerAxisArgs, Motion};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    enum Action {
+        Left,
+        Right,
+        Jump,
+    }
+
+    fn press(input: &mut GameInput, key: Key) {
+        input.on_button_event(ButtonArgs {
+            state: ButtonState::Press,
+            button: Keyboard(key),
+            scancode: None,
+        });
+    }
+
+    fn bindings() -> ActionMap<Action> {
+        let mut bindings = ActionMap::new();
+        bindings
+            .bind_key(Action::Left, Key::A)
+            .bind_gamepad_axis(Action::Left, 0, false)
+            .bind_key(Action::Right, Key::D)
+            .bind_gamepad_axis(Action::Right, 0, true);
+        bindings
+    }
+
+    #[test]
+    fn bindings_round_trip_through_strings() {
+        let cases = [
+            (InputBinding::Key(Key::Space), "Key:Space"),
+            (InputBinding::Key(Key::D1), "Key:D1"),
+            (InputBinding::Mouse(MouseButton::Left), "Mouse:Left"),
+            (InputBinding::Mouse(MouseButton::X1), "Mouse:X1"),
+            (InputBinding::GamepadButton(7), "Pad:7"),
+            (
+                InputBinding::GamepadAxis {
+                    axis: 0,
+                    positive: true,
+                },
+                "Axis:0+",
+            ),
+            (
+                InputBinding::GamepadAxis {
+                    axis: 3,
+                    positive: false,
+                },
+                "Axis:3-",
+            ),
+        ];
+        for &(binding, text) in cases.iter() {
+            assert_eq!(binding.to_string(), text);
+            assert_eq!(text.parse(), Ok(binding));
+        }
+    }
+
+    #[test]
+    fn unknown_bindings_are_rejected() {
+        for text in &[
+            "Space",
+            "Key:Nope",
+            "Mouse:Space",
+            "Pad:x",
+            "Axis:0",
+            "Joy:1",
+        ] {
+            assert_eq!(
+                text.parse::<InputBinding>(),
+                Err(format!("unknown input \"{}\"", text))
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_keys_are_still_read() {
+        let content = "[[bindings]]\n\
+                       action = \"Jump\"\n\
+                       inputs = [\"Pad:0\"]\n\
+                       keys = [\"Space\", \"W\"]\n";
+        let bindings: ActionMap<Action> =
+            ActionMap::parse(Path::new("bindings.toml"), content).unwrap();
+        assert_eq!(
+            bindings.inputs(Action::Jump),
+            &[
+                InputBinding::GamepadButton(0),
+                InputBinding::Key(Key::Space),
+                InputBinding::Key(Key::W),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_bindings_files_are_errors() {
+        let content = "[[bindings]]\naction = \"Jump\"\ninputs = [\"Key:Nope\"]\n";
+        match ActionMap::<Action>::parse(Path::new("bindings.toml"), content) {
+            Err(AssetError::Parse { message, .. }) => {
+                assert!(
+                    message.contains("unknown input \"Key:Nope\""),
+                    "{}",
+                    message
+                )
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        match ActionMap::<Action>::load("missing_bindings.toml") {
+            Err(AssetError::NotFound { .. }) => {}
+            other => panic!("expected a not found error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn axis_is_positive_minus_negative() {
+        let bindings = bindings();
+        let mut input = GameInput::new();
+        assert_eq!(
+            bindings.state(&input).axis(Action::Left, Action::Right),
+            0.0
+        );
+
+        press(&mut input, Key::A);
+        assert_eq!(
+            bindings.state(&input).axis(Action::Left, Action::Right),
+            -1.0
+        );
+
+        press(&mut input, Key::D);
+        assert_eq!(
+            bindings.state(&input).axis(Action::Left, Action::Right),
+            0.0
+        );
+    }
+
+    #[test]
+    fn axis_follows_the_stick() {
+        let bindings = bindings();
+        let mut input = GameInput::new();
+        input.set_dead_zone(0.0);
+        input.on_move_event(Motion::ControllerAxis(ControllerAxisArgs {
+            id: 0,
+            axis: 0,
+            position: -0.5,
+        }));
+        let state = bindings.state(&input);
+        assert_eq!(state.value(Action::Left), 0.5);
+        assert_eq!(state.axis(Action::Left, Action::Right), -0.5);
+        assert!(!state.is_active(Action::Right));
+    }
+}
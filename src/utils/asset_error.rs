@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::process;
+
+// Why an asset couldn't be loaded or looked up
+#[derive(Clone, Debug, PartialEq)]
+pub enum AssetError {
+    // The file or folder doesn't exist or can't be read
+    NotFound {
+        path: String,
+        reason: String,
+    },
+    // The file was read but its content is invalid, `line` and `column` start at 1
+    Parse {
+        path: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    // The sprite sheet has no sub-texture with this name
+    UnknownSprite {
+        name: String,
+    },
+}
+
+#[allow(dead_code)]
+impl AssetError {
+    pub fn not_found<E: fmt::Display>(path: &Path, reason: E) -> AssetError {
+        AssetError::NotFound {
+            path: path.display().to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn parse<E: fmt::Display>(
+        path: &Path,
+        location: Option<(usize, usize)>,
+        message: E,
+    ) -> AssetError {
+        AssetError::Parse {
+            path: path.display().to_string(),
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn unknown_sprite(name: &str) -> AssetError {
+        AssetError::UnknownSprite {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::NotFound { path, reason } => write!(f, "Cannot read {}: {}", path, reason),
+            AssetError::Parse {
+                path,
+                line: Some(line),
+                column,
+                message,
+            } => write!(
+                f,
+                "Invalid {} at line {}, column {}: {}",
+                path,
+                line,
+                column.unwrap_or(1),
+                message
+            ),
+            AssetError::Parse { path, message, .. } => write!(f, "Invalid {}: {}", path, message),
+            AssetError::UnknownSprite { name } => write!(f, "Unknown sprite {}", name),
+        }
+    }
+}
+
+impl Error for AssetError {}
+
+// Prints the error and quits, for the binaries that can't run without their assets
+#[allow(dead_code)]
+pub fn or_exit<T>(result: Result<T, AssetError>) -> T {
+    result.unwrap_or_else(|e| {
+        println!("{}", e);
+        process::exit(1);
+    })
+}
+
+// Line and column of a byte offset in `content`, both starting at 1
+#[allow(dead_code)]
+pub fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(newline) => before[newline + 1..].chars().count() + 1,
+        None => before.chars().count() + 1,
+    };
+    (line, column)
+}
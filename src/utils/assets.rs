@@ -0,0 +1,376 @@
+use piston_window::{
+    Flip, G2dTexture, G2dTextureContext, Glyphs, PistonWindow, Texture, TextureSettings,
+};
+use std::any::{Any, TypeId};
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+use utils::asset_error::AssetError;
+use utils::sprite_sheet::SpriteSheet;
+
+// Shared access to a loaded asset, clones of a handle point to the same asset
+pub struct Handle<T> {
+    asset: Rc<RefCell<T>>,
+    version: Rc<Cell<u32>>,
+}
+
+#[allow(dead_code)]
+impl<T> Handle<T> {
+    // Wraps an asset that wasn't loaded from a file, e.g. one built in code
+    pub fn new(asset: T) -> Handle<T> {
+        Handle {
+            asset: Rc::new(RefCell::new(asset)),
+            version: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn get(&self) -> Ref<'_, T> {
+        self.asset.borrow()
+    }
+
+    pub fn get_mut(&self) -> RefMut<'_, T> {
+        self.asset.borrow_mut()
+    }
+
+    // Goes up every time the asset is reloaded, so copies made of it can tell they're stale
+    pub fn version(&self) -> u32 {
+        self.version.get()
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        Handle {
+            asset: self.asset.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+// What the loaders can use besides the file
+pub struct LoadContext<'a> {
+    texture_context: Option<&'a mut G2dTextureContext>,
+    window: Option<&'a mut PistonWindow>,
+    strict: bool,
+    dependencies: Vec<PathBuf>,
+}
+
+#[allow(dead_code)]
+impl<'a> LoadContext<'a> {
+    // None when running without a window, textures can't be created then
+    pub fn texture_context(&mut self) -> Option<&mut G2dTextureContext> {
+        match self.texture_context {
+            Some(ref mut texture_context) => Some(&mut **texture_context),
+            None => None,
+        }
+    }
+
+    // For assets that need a texture context of their own, None without a window
+    pub fn create_texture_context(&mut self) -> Option<G2dTextureContext> {
+        self.window
+            .as_mut()
+            .map(|window| window.create_texture_context())
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    // Files the asset is made of besides its own, it's reloaded when they change too
+    pub fn add_dependency<P: AsRef<Path>>(&mut self, path: P) {
+        self.dependencies.push(path.as_ref().to_path_buf());
+    }
+}
+
+// Anything `Assets::load` can load from a file
+pub trait Asset: Sized + 'static {
+    fn load(path: &Path, context: &mut LoadContext) -> Result<Self, AssetError>;
+
+    // Takes over the reloaded asset, in place so every handle sees it. On error the asset is
+    // kept as it was.
+    fn reload(&mut self, asset: Self) -> Result<(), AssetError> {
+        *self = asset;
+        Ok(())
+    }
+}
+
+// Raw content of a sound file, decoding and playing it is left to the audio backend
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Sound {
+    pub data: Vec<u8>,
+}
+
+impl Asset for Sound {
+    fn load(path: &Path, _context: &mut LoadContext) -> Result<Sound, AssetError> {
+        let data = fs::read(path).map_err(|e| AssetError::not_found(path, e))?;
+        Ok(Sound { data })
+    }
+}
+
+impl Asset for SpriteSheet {
+    fn load(path: &Path, context: &mut LoadContext) -> Result<SpriteSheet, AssetError> {
+        let mut sprite_sheet = SpriteSheet::load(path, None, context.texture_context())?;
+        sprite_sheet.set_strict(context.is_strict());
+        let folder = path.parent().unwrap_or_else(|| Path::new(""));
+        context.add_dependency(folder.join(sprite_sheet.image_path()));
+        Ok(sprite_sheet)
+    }
+
+    // Keeps the sprite ids that were looked up before valid
+    fn reload(&mut self, sprite_sheet: SpriteSheet) -> Result<(), AssetError> {
+        self.reload_from(sprite_sheet)
+    }
+}
+
+impl Asset for Glyphs {
+    fn load(path: &Path, context: &mut LoadContext) -> Result<Glyphs, AssetError> {
+        if !path.is_file() {
+            return Err(AssetError::not_found(path, "no such file"));
+        }
+        let texture_context = context.create_texture_context().ok_or_else(|| {
+            AssetError::parse(path, None, "fonts can't be loaded without a window")
+        })?;
+        Glyphs::new(path, texture_context, TextureSettings::new())
+            .map_err(|e| AssetError::parse(path, None, e))
+    }
+}
+
+// A single image, for what isn't part of a sprite sheet
+impl Asset for G2dTexture {
+    fn load(path: &Path, context: &mut LoadContext) -> Result<G2dTexture, AssetError> {
+        if !path.is_file() {
+            return Err(AssetError::not_found(path, "no such file"));
+        }
+        let texture_context = context.texture_context().ok_or_else(|| {
+            AssetError::parse(path, None, "textures can't be loaded without a window")
+        })?;
+        Texture::from_path(texture_context, path, Flip::None, &TextureSettings::new())
+            .map_err(|e| AssetError::parse(path, None, e))
+    }
+}
+
+// Loads the asset again from the path and replaces it in its handle
+type ReloadFn = Box<dyn Fn(&Path, &mut LoadContext) -> Result<(), AssetError>>;
+
+// A cached asset, with what's needed to notice its files changed and to reload it
+struct Entry {
+    handle: Box<dyn Any>,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    reload: ReloadFn,
+}
+
+// Loads every asset of the game from the assets folder, which is looked up once when it's
+// created. Assets are loaded the first time they are asked for, later requests for the same
+// path and type get a handle to the already loaded one.
+pub struct Assets {
+    root: PathBuf,
+    texture_context: Option<G2dTextureContext>,
+    strict: bool,
+    cache: HashMap<(TypeId, PathBuf), Entry>,
+}
+
+#[allow(dead_code)]
+impl Assets {
+    // `folder` is searched for in the parents and the children of the working directory
+    pub fn new(folder: &str) -> Result<Assets, AssetError> {
+        let root = find_folder::Search::ParentsThenKids(3, 3)
+            .for_folder(folder)
+            .map_err(|e| AssetError::not_found(Path::new(folder), e))?;
+
+        Ok(Assets {
+            root,
+            texture_context: None,
+            strict: false,
+            cache: HashMap::new(),
+        })
+    }
+
+    // Sprite sheets are loaded without their textures until this is set, which is what the
+    // headless runner needs
+    pub fn set_texture_context(&mut self, texture_context: G2dTextureContext) -> &mut Assets {
+        self.texture_context = Some(texture_context);
+        self
+    }
+
+    // Sprite sheets loaded afterwards are strict, see `SpriteSheet::set_strict`
+    pub fn set_strict(&mut self, strict: bool) -> &mut Assets {
+        self.strict = strict;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Relative paths are relative to the assets folder, absolute ones are kept as they are
+    pub fn path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.root.join(path)
+    }
+
+    pub fn load<T: Asset, P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<T>, AssetError> {
+        self.load_with(path, T::load)
+    }
+
+    // Loads the asset with a custom loader, e.g. a sprite sheet with an `AtlasLoader` of its
+    // own. The loader isn't part of the cache key, the first load of a path decides.
+    pub fn load_with<T, P, F>(&mut self, path: P, load: F) -> Result<Handle<T>, AssetError>
+    where
+        T: Asset,
+        P: AsRef<Path>,
+        F: Fn(&Path, &mut LoadContext) -> Result<T, AssetError> + 'static,
+    {
+        let path = self.path(path);
+        self.load_in(path, None, load)
+    }
+
+    pub fn sprite_sheet<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Handle<SpriteSheet>, AssetError> {
+        self.load(path)
+    }
+
+    pub fn texture<P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<G2dTexture>, AssetError> {
+        self.load(path)
+    }
+
+    pub fn sound<P: AsRef<Path>>(&mut self, path: P) -> Result<Handle<Sound>, AssetError> {
+        self.load(path)
+    }
+
+    // The glyph cache needs a texture context of its own, which is made from the window
+    pub fn font<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        window: &mut PistonWindow,
+    ) -> Result<Handle<Glyphs>, AssetError> {
+        let path = self.path(path);
+        self.load_in(path, Some(window), Glyphs::load)
+    }
+
+    // Reloads the assets whose files were modified since they were loaded, fonts only if
+    // there's a window to make their texture context with. An asset that fails to load is
+    // logged and kept as it was until its files change again. Returns how many were reloaded.
+    pub fn reload_changed(&mut self, mut window: Option<&mut PistonWindow>) -> usize {
+        let mut reloaded = 0;
+        for ((_, path), entry) in self.cache.iter_mut() {
+            let changed = entry
+                .files
+                .iter()
+                .any(|&(ref file, modified)| modified_time(file) != modified);
+            if !changed {
+                continue;
+            }
+
+            let mut context = LoadContext {
+                texture_context: self.texture_context.as_mut(),
+                window: window.as_deref_mut(),
+                strict: self.strict,
+                dependencies: Vec::new(),
+            };
+            match (entry.reload)(path, &mut context) {
+                Ok(()) => {
+                    println!("Reloaded {}", path.display());
+                    reloaded += 1;
+                    entry.files = watched_files(path, context.dependencies);
+                }
+                Err(e) => {
+                    println!("Cannot reload {}: {}", path.display(), e);
+                    // the failed attempt may not have got as far as its dependencies, so the
+                    // files watched before are kept to retry once any of them changes again
+                    for &mut (ref file, ref mut modified) in entry.files.iter_mut() {
+                        *modified = modified_time(file);
+                    }
+                }
+            }
+        }
+        reloaded
+    }
+
+    fn load_in<T, F>(
+        &mut self,
+        path: PathBuf,
+        window: Option<&mut PistonWindow>,
+        load: F,
+    ) -> Result<Handle<T>, AssetError>
+    where
+        T: Asset,
+        F: Fn(&Path, &mut LoadContext) -> Result<T, AssetError> + 'static,
+    {
+        let key = (TypeId::of::<T>(), path);
+        if let Some(handle) = self
+            .cache
+            .get(&key)
+            .and_then(|entry| entry.handle.downcast_ref::<Handle<T>>())
+        {
+            return Ok(handle.clone());
+        }
+
+        let mut context = LoadContext {
+            texture_context: self.texture_context.as_mut(),
+            window,
+            strict: self.strict,
+            dependencies: Vec::new(),
+        };
+        let handle = Handle::new(load(&key.1, &mut context)?);
+        let files = watched_files(&key.1, context.dependencies);
+
+        let reloaded = handle.clone();
+        let reload = move |path: &Path, context: &mut LoadContext| {
+            let asset = load(path, context)?;
+            reloaded.get_mut().reload(asset)?;
+            reloaded.version.set(reloaded.version.get() + 1);
+            Ok(())
+        };
+        self.cache.insert(
+            key,
+            Entry {
+                handle: Box::new(handle.clone()),
+                files,
+                reload: Box::new(reload),
+            },
+        );
+        Ok(handle)
+    }
+}
+
+// The asset's own file and its dependencies, with when they were last modified
+fn watched_files(path: &Path, dependencies: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    Some(path.to_path_buf())
+        .into_iter()
+        .chain(dependencies)
+        .map(|file| {
+            let modified = modified_time(&file);
+            (file, modified)
+        })
+        .collect()
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::game_window::ASSETS_FOLDER;
+
+    #[test]
+    fn textures_need_a_window() {
+        let mut assets = Assets::new(ASSETS_FOLDER).unwrap();
+        match assets.texture("sprites.png") {
+            Err(AssetError::Parse { message, .. }) => {
+                assert_eq!(message, "textures can't be loaded without a window")
+            }
+            _ => panic!("expected a parse error"),
+        }
+        match assets.texture("missing.png") {
+            Err(AssetError::NotFound { .. }) => {}
+            _ => panic!("expected a not found error"),
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use utils::asset_error::{line_column, AssetError};
+
+// The sprites of a sprite sheet and the image they are in, whatever format it was loaded from
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureAtlas {
+    // Relative to the descriptor's folder
+    pub image_path: String,
+    #[serde(rename = "SubTexture")]
+    pub sub_textures: Vec<SubTexture>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SubTexture {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    // The trimmed area's offset inside the frame, zero or negative
+    #[serde(default)]
+    pub frame_x: f64,
+    #[serde(default)]
+    pub frame_y: f64,
+    // Size of the untrimmed sprite, missing if it wasn't trimmed
+    pub frame_width: Option<f64>,
+    pub frame_height: Option<f64>,
+    // Stored turned 90° clockwise, `width` and `height` are the size in the atlas
+    #[serde(default)]
+    pub rotated: bool,
+}
+
+#[allow(dead_code)]
+impl SubTexture {
+    pub fn frame_size(&self) -> [f64; 2] {
+        let (width, height) = if self.rotated {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+        [
+            self.frame_width.unwrap_or(width),
+            self.frame_height.unwrap_or(height),
+        ]
+    }
+}
+
+// Turns the content of a sprite sheet descriptor into a `TextureAtlas`, implement it to load
+// sprite sheets of other formats with `SpriteSheet::load`
+pub trait AtlasLoader {
+    fn parse(&self, path: &Path, content: &str) -> Result<TextureAtlas, AssetError>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AtlasFormat {
+    // `<TextureAtlas>` XML written by ShoeBox, Starling and most other packers
+    Starling,
+    // JSON hash or array written by TexturePacker
+    TexturePacker,
+    // JSON written by Aseprite's "Export Sprite Sheet", laid out like TexturePacker's
+    Aseprite,
+    // TOML describing a uniform grid of sprites, see `GridFile`
+    Grid,
+}
+
+#[allow(dead_code)]
+impl AtlasFormat {
+    // Guessed from the extension, JSON files are told apart by the app that wrote them
+    pub fn detect(path: &Path, content: &str) -> Option<AtlasFormat> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "xml" => Some(AtlasFormat::Starling),
+            "toml" => Some(AtlasFormat::Grid),
+            "json" if content.contains("aseprite") => Some(AtlasFormat::Aseprite),
+            "json" => Some(AtlasFormat::TexturePacker),
+            _ => None,
+        }
+    }
+}
+
+impl AtlasLoader for AtlasFormat {
+    fn parse(&self, path: &Path, content: &str) -> Result<TextureAtlas, AssetError> {
+        match self {
+            AtlasFormat::Starling => parse_starling(path, content),
+            AtlasFormat::TexturePacker | AtlasFormat::Aseprite => parse_json(path, content),
+            AtlasFormat::Grid => parse_grid(path, content),
+        }
+    }
+}
+
+fn parse_starling(path: &Path, content: &str) -> Result<TextureAtlas, AssetError> {
+    quick_xml::de::from_str(content)
+        .map_err(|e| AssetError::parse(path, xml_error_location(content), e))
+}
+
+// The XML deserializer doesn't report where it failed, so syntax errors are located by
+// reading the file again event by event
+fn xml_error_location(content: &str) -> Option<(usize, usize)> {
+    let mut reader = Reader::from_str(content);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Eof) => return None,
+            Ok(_) => buf.clear(),
+            Err(_) => return Some(line_column(content, reader.buffer_position())),
+        }
+    }
+}
+
+// TexturePacker and Aseprite share the format, the frames are either an object keyed by the
+// frame names or an array of frames with a `filename` each
+#[derive(Deserialize)]
+struct JsonAtlas {
+    frames: JsonFrames,
+    meta: JsonMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonFrames {
+    Hash(BTreeMap<String, JsonFrame>),
+    Array(Vec<JsonNamedFrame>),
+}
+
+#[derive(Deserialize)]
+struct JsonNamedFrame {
+    filename: String,
+    #[serde(flatten)]
+    frame: JsonFrame,
+}
+
+// `frame` is the sprite's area in the image, with the size it has before being rotated
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrame {
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<JsonRect>,
+    source_size: Option<JsonSize>,
+}
+
+#[derive(Deserialize)]
+struct JsonRect {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+#[derive(Deserialize)]
+struct JsonSize {
+    w: f64,
+    h: f64,
+}
+
+#[derive(Deserialize)]
+struct JsonMeta {
+    image: String,
+}
+
+impl JsonFrame {
+    fn into_sub_texture(self, name: String) -> SubTexture {
+        let (width, height) = if self.rotated {
+            (self.frame.h, self.frame.w)
+        } else {
+            (self.frame.w, self.frame.h)
+        };
+        let offset = match self.sprite_source_size {
+            Some(ref rect) if self.trimmed => [rect.x, rect.y],
+            _ => [0.0, 0.0],
+        };
+        let source_size = if self.trimmed { self.source_size } else { None };
+
+        SubTexture {
+            name,
+            x: self.frame.x,
+            y: self.frame.y,
+            width,
+            height,
+            frame_x: -offset[0],
+            frame_y: -offset[1],
+            frame_width: source_size.as_ref().map(|size| size.w),
+            frame_height: source_size.as_ref().map(|size| size.h),
+            rotated: self.rotated,
+        }
+    }
+}
+
+fn parse_json(path: &Path, content: &str) -> Result<TextureAtlas, AssetError> {
+    let atlas: JsonAtlas = serde_json::from_str(content).map_err(|e| {
+        let location = Some((e.line(), e.column())).filter(|&(line, _)| line > 0);
+        AssetError::parse(path, location, e)
+    })?;
+
+    let sub_textures = match atlas.frames {
+        JsonFrames::Hash(frames) => frames
+            .into_iter()
+            .map(|(name, frame)| frame.into_sub_texture(name))
+            .collect(),
+        JsonFrames::Array(frames) => frames
+            .into_iter()
+            .map(|frame| frame.frame.into_sub_texture(frame.filename))
+            .collect(),
+    };
+
+    Ok(TextureAtlas {
+        image_path: atlas.meta.image,
+        sub_textures,
+    })
+}
+
+// E.g. for a 8x4 grid of 16px tiles with a 1px gap between them:
+//
+//   image = "tiles.png"
+//   tile_width = 16
+//   tile_height = 16
+//   columns = 8
+//   rows = 4
+//   spacing = 1
+//
+// The sprites are named by `names` row by row, the rest by `prefix` and their index.
+#[derive(Deserialize)]
+struct GridFile {
+    image: String,
+    tile_width: u32,
+    tile_height: u32,
+    columns: u32,
+    rows: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    prefix: String,
+    #[serde(default)]
+    names: Vec<String>,
+}
+
+fn parse_grid(path: &Path, content: &str) -> Result<TextureAtlas, AssetError> {
+    let grid: GridFile = toml::from_str(content).map_err(|e| {
+        let location = e.line_col().map(|(line, column)| (line + 1, column + 1));
+        AssetError::parse(path, location, e)
+    })?;
+
+    let mut sub_textures = Vec::new();
+    for row in 0..grid.rows {
+        for column in 0..grid.columns {
+            let index = (row * grid.columns + column) as usize;
+            let name = match grid.names.get(index) {
+                Some(name) => name.clone(),
+                None => format!("{}{}", grid.prefix, index),
+            };
+            sub_textures.push(SubTexture {
+                name,
+                x: (grid.margin + column * (grid.tile_width + grid.spacing)) as f64,
+                y: (grid.margin + row * (grid.tile_height + grid.spacing)) as f64,
+                width: grid.tile_width as f64,
+                height: grid.tile_height as f64,
+                frame_x: 0.0,
+                frame_y: 0.0,
+                frame_width: None,
+                frame_height: None,
+                rotated: false,
+            });
+        }
+    }
+
+    Ok(TextureAtlas {
+        image_path: grid.image,
+        sub_textures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STARLING: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TextureAtlas imagePath="sprites.png">
+    <SubTexture name="grass.png" x="0" y="0" width="64" height="64"/>
+    <SubTexture name="coin.png" x="64" y="0" width="30" height="28"
+        frameX="-2" frameY="-4" frameWidth="34" frameHeight="36"/>
+    <SubTexture name="key.png" x="94" y="0" width="20" height="40" rotated="true"/>
+</TextureAtlas>
+"#;
+
+    const TEXTURE_PACKER_HASH: &str = r#"{
+    "frames": {
+        "grass.png": {
+            "frame": {"x": 0, "y": 0, "w": 64, "h": 64},
+            "rotated": false,
+            "trimmed": false,
+            "spriteSourceSize": {"x": 0, "y": 0, "w": 64, "h": 64},
+            "sourceSize": {"w": 64, "h": 64}
+        },
+        "coin.png": {
+            "frame": {"x": 64, "y": 0, "w": 30, "h": 28},
+            "rotated": false,
+            "trimmed": true,
+            "spriteSourceSize": {"x": 2, "y": 4, "w": 30, "h": 28},
+            "sourceSize": {"w": 34, "h": 36}
+        },
+        "key.png": {
+            "frame": {"x": 94, "y": 0, "w": 40, "h": 20},
+            "rotated": true,
+            "trimmed": false
+        }
+    },
+    "meta": {"app": "https://www.codeandweb.com/texturepacker", "image": "sprites.png"}
+}"#;
+
+    const ASEPRITE_ARRAY: &str = r#"{
+    "frames": [
+        {
+            "filename": "walk 0.aseprite",
+            "frame": {"x": 0, "y": 0, "w": 16, "h": 24},
+            "rotated": false,
+            "trimmed": false,
+            "spriteSourceSize": {"x": 0, "y": 0, "w": 16, "h": 24},
+            "sourceSize": {"w": 16, "h": 24},
+            "duration": 100
+        },
+        {
+            "filename": "walk 1.aseprite",
+            "frame": {"x": 16, "y": 0, "w": 14, "h": 22},
+            "rotated": false,
+            "trimmed": true,
+            "spriteSourceSize": {"x": 1, "y": 2, "w": 14, "h": 22},
+            "sourceSize": {"w": 16, "h": 24},
+            "duration": 100
+        }
+    ],
+    "meta": {"app": "http://www.aseprite.org/", "image": "walk.png"}
+}"#;
+
+    const GRID: &str = r#"
+image = "tiles.png"
+tile_width = 16
+tile_height = 8
+columns = 3
+rows = 2
+margin = 2
+spacing = 1
+prefix = "tile_"
+names = ["grass", "dirt"]
+"#;
+
+    fn sprite<'a>(atlas: &'a TextureAtlas, name: &str) -> &'a SubTexture {
+        atlas
+            .sub_textures
+            .iter()
+            .find(|sprite| sprite.name == name)
+            .unwrap_or_else(|| panic!("{} is missing", name))
+    }
+
+    fn parse(file: &str, content: &str) -> TextureAtlas {
+        let path = Path::new(file);
+        AtlasFormat::detect(path, content)
+            .unwrap()
+            .parse(path, content)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn detects_the_format() {
+        let detect = |file: &str, content: &str| AtlasFormat::detect(Path::new(file), content);
+        assert_eq!(detect("a.xml", STARLING), Some(AtlasFormat::Starling));
+        assert_eq!(detect("a.XML", STARLING), Some(AtlasFormat::Starling));
+        assert_eq!(detect("a.toml", GRID), Some(AtlasFormat::Grid));
+        assert_eq!(
+            detect("a.json", TEXTURE_PACKER_HASH),
+            Some(AtlasFormat::TexturePacker)
+        );
+        assert_eq!(
+            detect("a.json", ASEPRITE_ARRAY),
+            Some(AtlasFormat::Aseprite)
+        );
+        assert_eq!(detect("a.png", ""), None);
+        assert_eq!(detect("atlas", ""), None);
+    }
+
+    #[test]
+    fn parses_starling_xml() {
+        let atlas = parse("sprites.xml", STARLING);
+        assert_eq!(atlas.image_path, "sprites.png");
+        assert_eq!(atlas.sub_textures.len(), 3);
+
+        let grass = sprite(&atlas, "grass.png");
+        assert_eq!(
+            [grass.x, grass.y, grass.width, grass.height],
+            [0.0, 0.0, 64.0, 64.0]
+        );
+        assert_eq!(grass.frame_size(), [64.0, 64.0]);
+
+        let coin = sprite(&atlas, "coin.png");
+        assert_eq!([coin.frame_x, coin.frame_y], [-2.0, -4.0]);
+        assert_eq!(coin.frame_size(), [34.0, 36.0]);
+
+        let key = sprite(&atlas, "key.png");
+        assert!(key.rotated);
+        assert_eq!(key.frame_size(), [40.0, 20.0]);
+    }
+
+    #[test]
+    fn parses_texture_packer_hash() {
+        let atlas = parse("sprites.json", TEXTURE_PACKER_HASH);
+        assert_eq!(atlas.image_path, "sprites.png");
+        assert_eq!(atlas.sub_textures.len(), 3);
+
+        // untrimmed frames don't get a frame size even if the file has one
+        let grass = sprite(&atlas, "grass.png");
+        assert_eq!([grass.frame_x, grass.frame_y], [0.0, 0.0]);
+        assert_eq!(grass.frame_width, None);
+        assert_eq!(grass.frame_size(), [64.0, 64.0]);
+
+        let coin = sprite(&atlas, "coin.png");
+        assert_eq!(
+            [coin.x, coin.y, coin.width, coin.height],
+            [64.0, 0.0, 30.0, 28.0]
+        );
+        assert_eq!([coin.frame_x, coin.frame_y], [-2.0, -4.0]);
+        assert_eq!(coin.frame_size(), [34.0, 36.0]);
+
+        // the frame is the rotated area in the image, the sprite keeps its own size
+        let key = sprite(&atlas, "key.png");
+        assert!(key.rotated);
+        assert_eq!([key.width, key.height], [20.0, 40.0]);
+        assert_eq!(key.frame_size(), [40.0, 20.0]);
+    }
+
+    #[test]
+    fn parses_aseprite_array() {
+        let atlas = parse("walk.json", ASEPRITE_ARRAY);
+        assert_eq!(atlas.image_path, "walk.png");
+        let names: Vec<&str> = atlas
+            .sub_textures
+            .iter()
+            .map(|sprite| sprite.name.as_str())
+            .collect();
+        assert_eq!(names, ["walk 0.aseprite", "walk 1.aseprite"]);
+
+        let trimmed = sprite(&atlas, "walk 1.aseprite");
+        assert_eq!([trimmed.frame_x, trimmed.frame_y], [-1.0, -2.0]);
+        assert_eq!(trimmed.frame_size(), [16.0, 24.0]);
+    }
+
+    #[test]
+    fn parses_grid() {
+        let atlas = parse("tiles.toml", GRID);
+        assert_eq!(atlas.image_path, "tiles.png");
+        let names: Vec<&str> = atlas
+            .sub_textures
+            .iter()
+            .map(|sprite| sprite.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["grass", "dirt", "tile_2", "tile_3", "tile_4", "tile_5"]
+        );
+
+        // margin around the grid, spacing between the tiles
+        let dirt = sprite(&atlas, "dirt");
+        assert_eq!(
+            [dirt.x, dirt.y, dirt.width, dirt.height],
+            [19.0, 2.0, 16.0, 8.0]
+        );
+        let last = sprite(&atlas, "tile_5");
+        assert_eq!([last.x, last.y], [36.0, 11.0]);
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        let broken = "{\n    \"frames\": {},\n    \"meta\": {\"image\": }\n}";
+        let error = AtlasFormat::TexturePacker
+            .parse(Path::new("broken.json"), broken)
+            .unwrap_err();
+        match error {
+            AssetError::Parse {
+                line, column, path, ..
+            } => {
+                assert_eq!(path, "broken.json");
+                assert_eq!(line, Some(3));
+                assert!(column.is_some());
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let broken = "image = \"tiles.png\"\ntile_width = \"wide\"\n";
+        match AtlasFormat::Grid.parse(Path::new("broken.toml"), broken) {
+            Err(AssetError::Parse { line, .. }) => assert_eq!(line, Some(2)),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let broken = "<TextureAtlas imagePath=\"a.png\">\n<SubTexture name=\"a\"/>\n</Atlas>";
+        match AtlasFormat::Starling.parse(Path::new("broken.xml"), broken) {
+            Err(AssetError::Parse { line, .. }) => assert_eq!(line, Some(3)),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use piston_window::math::Matrix2d;
+use piston_window::*;
+
+// A textured rectangle of `Canvas::quads`, drawn like `Canvas::image` would draw it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quad {
+    pub color: [f32; 4],
+    pub src_rect: [f64; 4],
+    pub transform: Matrix2d,
+}
+
+// Everything the scenes draw goes through this, so they can be rendered either into a real
+// window or into a `RecordingCanvas` when running without one
+pub trait Canvas {
+    fn clear(&mut self, color: [f32; 4]);
+    fn rectangle(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d);
+    #[allow(dead_code)]
+    fn ellipse(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d);
+    // The texture's colors are multiplied by `color`, white draws it unchanged
+    fn image(
+        &mut self,
+        texture: Option<&G2dTexture>,
+        color: [f32; 4],
+        src_rect: [f64; 4],
+        transform: Matrix2d,
+    );
+    // Draws all the quads from `texture` in one go, see `SpriteBatch`
+    fn quads(&mut self, texture: Option<&G2dTexture>, quads: &[Quad]);
+    fn text(&mut self, color: [f32; 4], font_size: u32, text: &str, transform: Matrix2d);
+}
+
+pub struct G2dCanvas<'a, 'b: 'a> {
+    g: &'a mut G2d<'b>,
+    glyphs: &'a mut Glyphs,
+    draw_state: DrawState,
+}
+
+impl<'a, 'b> G2dCanvas<'a, 'b> {
+    pub fn new(g: &'a mut G2d<'b>, glyphs: &'a mut Glyphs, c: &Context) -> G2dCanvas<'a, 'b> {
+        G2dCanvas {
+            g,
+            glyphs,
+            draw_state: c.draw_state,
+        }
+    }
+}
+
+impl<'a, 'b> Canvas for G2dCanvas<'a, 'b> {
+    fn clear(&mut self, color: [f32; 4]) {
+        clear(color, self.g);
+    }
+
+    fn rectangle(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d) {
+        Rectangle::new(color).draw(rect, &self.draw_state, transform, self.g);
+    }
+
+    fn ellipse(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d) {
+        Ellipse::new(color).draw(rect, &self.draw_state, transform, self.g);
+    }
+
+    fn image(
+        &mut self,
+        texture: Option<&G2dTexture>,
+        color: [f32; 4],
+        src_rect: [f64; 4],
+        transform: Matrix2d,
+    ) {
+        if let Some(texture) = texture {
+            Image::new_color(color).src_rect(src_rect).draw(
+                texture,
+                &self.draw_state,
+                transform,
+                self.g,
+            );
+        }
+    }
+
+    // One draw call per run of quads with the same color, split up where the vertices wouldn't
+    // fit into the back end's buffer
+    fn quads(&mut self, texture: Option<&G2dTexture>, quads: &[Quad]) {
+        let texture = match texture {
+            Some(texture) => texture,
+            None => return,
+        };
+        let mut start = 0;
+        while start < quads.len() {
+            let color = quads[start].color;
+            let end = quads[start..]
+                .iter()
+                .position(|quad| quad.color != color)
+                .map_or(quads.len(), |count| start + count);
+            let run = &quads[start..end];
+            self.g.tri_list_uv(&self.draw_state, &color, texture, |f| {
+                for chunk in run.chunks(BACK_END_MAX_VERTEX_COUNT / 6) {
+                    let mut vertices = Vec::with_capacity(chunk.len() * 6);
+                    let mut uvs = Vec::with_capacity(chunk.len() * 6);
+                    for quad in chunk {
+                        let rect = [0.0, 0.0, quad.src_rect[2], quad.src_rect[3]];
+                        vertices.extend_from_slice(&triangulation::rect_tri_list_xy(
+                            quad.transform,
+                            rect,
+                        ));
+                        uvs.extend_from_slice(&triangulation::rect_tri_list_uv(
+                            texture,
+                            quad.src_rect,
+                        ));
+                    }
+                    f(&vertices, &uvs);
+                }
+            });
+            start = end;
+        }
+    }
+
+    fn text(&mut self, color: [f32; 4], font_size: u32, text: &str, transform: Matrix2d) {
+        text::Text::new_color(color, font_size)
+            .draw(text, self.glyphs, &self.draw_state, transform, self.g)
+            .unwrap();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    Clear {
+        color: [f32; 4],
+    },
+    Rectangle {
+        color: [f32; 4],
+        rect: [f64; 4],
+        transform: Matrix2d,
+    },
+    #[allow(dead_code)]
+    Ellipse {
+        color: [f32; 4],
+        rect: [f64; 4],
+        transform: Matrix2d,
+    },
+    Image {
+        color: [f32; 4],
+        src_rect: [f64; 4],
+        transform: Matrix2d,
+    },
+    // A whole batch is one command, however many sprites it has
+    Quads {
+        quads: Vec<Quad>,
+    },
+    Text {
+        color: [f32; 4],
+        font_size: u32,
+        text: String,
+        transform: Matrix2d,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordingCanvas {
+    pub commands: Vec<DrawCommand>,
+}
+
+#[allow(dead_code)]
+impl RecordingCanvas {
+    pub fn new() -> RecordingCanvas {
+        RecordingCanvas {
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl Canvas for RecordingCanvas {
+    fn clear(&mut self, color: [f32; 4]) {
+        self.commands.push(DrawCommand::Clear { color });
+    }
+
+    fn rectangle(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d) {
+        self.commands.push(DrawCommand::Rectangle {
+            color,
+            rect,
+            transform,
+        });
+    }
+
+    fn ellipse(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d) {
+        self.commands.push(DrawCommand::Ellipse {
+            color,
+            rect,
+            transform,
+        });
+    }
+
+    fn image(
+        &mut self,
+        _texture: Option<&G2dTexture>,
+        color: [f32; 4],
+        src_rect: [f64; 4],
+        transform: Matrix2d,
+    ) {
+        self.commands.push(DrawCommand::Image {
+            color,
+            src_rect,
+            transform,
+        });
+    }
+
+    fn quads(&mut self, _texture: Option<&G2dTexture>, quads: &[Quad]) {
+        self.commands.push(DrawCommand::Quads {
+            quads: quads.to_vec(),
+        });
+    }
+
+    fn text(&mut self, color: [f32; 4], font_size: u32, text: &str, transform: Matrix2d) {
+        self.commands.push(DrawCommand::Text {
+            color,
+            font_size,
+            text: text.to_string(),
+            transform,
+        });
+    }
+}
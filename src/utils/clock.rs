@@ -0,0 +1,195 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Instant;
+
+pub trait Clock {
+    // Seconds elapsed since the clock was created, never goes backwards
+    fn now(&self) -> f64;
+}
+
+pub struct RealClock {
+    start: Instant,
+}
+
+#[allow(dead_code)]
+impl RealClock {
+    pub fn new() -> RealClock {
+        RealClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for RealClock {
+    fn default() -> RealClock {
+        RealClock::new()
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+}
+
+// Only moves when told to. Clones share the same time, so one copy can be handed over to
+// `GameWindow` while another one is used to drive it.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    time: Rc<Cell<f64>>,
+}
+
+#[allow(dead_code)]
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock {
+            time: Rc::new(Cell::new(0.0)),
+        }
+    }
+
+    pub fn advance(&self, dt: f64) {
+        self.time.set(self.time.get() + dt.max(0.0));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.time.get()
+    }
+}
+
+#[derive(Clone)]
+pub struct TimeScale {
+    scale: Rc<Cell<f64>>,
+}
+
+#[allow(dead_code)]
+impl TimeScale {
+    pub fn get(&self) -> f64 {
+        self.scale.get()
+    }
+
+    // 0.0 pauses the clock, values below 1.0 slow it down, above 1.0 fast-forward it
+    pub fn set(&self, scale: f64) {
+        self.scale.set(scale.max(0.0));
+    }
+}
+
+pub struct ScaledClock<C: Clock> {
+    inner: C,
+    scale: TimeScale,
+    last_inner: Cell<f64>,
+    time: Cell<f64>,
+}
+
+#[allow(dead_code)]
+impl<C: Clock> ScaledClock<C> {
+    pub fn new(inner: C) -> ScaledClock<C> {
+        let last_inner = inner.now();
+        ScaledClock {
+            inner,
+            scale: TimeScale {
+                scale: Rc::new(Cell::new(1.0)),
+            },
+            last_inner: Cell::new(last_inner),
+            time: Cell::new(0.0),
+        }
+    }
+
+    // The returned handle can be kept by the scenes to change the speed of the game
+    pub fn time_scale(&self) -> TimeScale {
+        self.scale.clone()
+    }
+}
+
+impl<C: Clock> Clock for ScaledClock<C> {
+    fn now(&self) -> f64 {
+        let inner = self.inner.now();
+        let time = self.time.get() + (inner - self.last_inner.get()) * self.scale.get();
+        self.last_inner.set(inner);
+        self.time.set(time);
+        time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_only_moves_forward_when_advanced() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.now(), 0.0);
+
+        clock.advance(0.5);
+        clock.advance(0.25);
+        assert_eq!(clock.now(), 0.75);
+
+        clock.advance(-1.0);
+        assert_eq!(clock.now(), 0.75);
+    }
+
+    #[test]
+    fn manual_clock_clones_share_the_time() {
+        let clock = ManualClock::new();
+        let driver = clock.clone();
+
+        driver.advance(2.0);
+        assert_eq!(clock.now(), 2.0);
+    }
+
+    #[test]
+    fn scaled_clock_follows_its_inner_clock() {
+        let inner = ManualClock::new();
+        inner.advance(10.0);
+        let clock = ScaledClock::new(inner.clone());
+        assert_eq!(clock.now(), 0.0);
+
+        inner.advance(1.5);
+        assert_eq!(clock.now(), 1.5);
+    }
+
+    #[test]
+    fn scale_changes_only_affect_the_time_after_them() {
+        let inner = ManualClock::new();
+        let clock = ScaledClock::new(inner.clone());
+        let scale = clock.time_scale();
+
+        scale.set(2.0);
+        inner.advance(1.0);
+        assert_eq!(clock.now(), 2.0);
+
+        scale.set(0.5);
+        inner.advance(1.0);
+        assert_eq!(clock.now(), 2.5);
+        assert_eq!(scale.get(), 0.5);
+    }
+
+    #[test]
+    fn zero_scale_pauses_the_clock() {
+        let inner = ManualClock::new();
+        let clock = ScaledClock::new(inner.clone());
+        let scale = clock.time_scale();
+        inner.advance(1.0);
+        assert_eq!(clock.now(), 1.0);
+
+        scale.set(0.0);
+        inner.advance(5.0);
+        assert_eq!(clock.now(), 1.0);
+
+        scale.set(1.0);
+        inner.advance(0.5);
+        assert_eq!(clock.now(), 1.5);
+    }
+
+    #[test]
+    fn negative_scales_pause_instead_of_rewinding() {
+        let inner = ManualClock::new();
+        let clock = ScaledClock::new(inner.clone());
+        clock.time_scale().set(-1.0);
+
+        inner.advance(1.0);
+        assert_eq!(clock.time_scale().get(), 0.0);
+        assert_eq!(clock.now(), 0.0);
+    }
+}
@@ -0,0 +1,394 @@
+use piston::Key;
+use piston_window::{OpenGL, WindowSettings};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Deserialize;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use utils::replay::Recording;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub opengl: String,
+    pub resizable: bool,
+    pub exit_on_esc: bool,
+}
+
+#[allow(dead_code)]
+impl WindowConfig {
+    pub fn new(title: &str, width: u32, height: u32) -> WindowConfig {
+        WindowConfig {
+            title: title.to_string(),
+            width,
+            height,
+            opengl: "3.2".to_string(),
+            resizable: true,
+            exit_on_esc: true,
+        }
+    }
+
+    pub fn set_resizable(&mut self, resizable: bool) -> &mut WindowConfig {
+        self.resizable = resizable;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BallConfig {
+    pub count: u32,
+    pub min_velocity: f64,
+    pub max_velocity: f64,
+    pub min_radius: f64,
+    pub max_radius: f64,
+}
+
+impl Default for BallConfig {
+    fn default() -> BallConfig {
+        BallConfig {
+            count: 100,
+            min_velocity: 500.0,
+            max_velocity: 1000.0,
+            min_radius: 10.0,
+            max_radius: 20.0,
+        }
+    }
+}
+
+impl BallConfig {
+    // The ranges the balls' velocity and radius are picked from must be positive and not empty
+    fn validate(&self) -> Result<(), String> {
+        let values = [
+            ("min_velocity", self.min_velocity),
+            ("max_velocity", self.max_velocity),
+            ("min_radius", self.min_radius),
+            ("max_radius", self.max_radius),
+        ];
+        for &(name, value) in values.iter() {
+            if value <= 0.0 || value.is_nan() {
+                return Err(format!("balls.{} must be positive, got {}", name, value));
+            }
+        }
+        let ranges = [
+            ("velocity", self.min_velocity, self.max_velocity),
+            ("radius", self.min_radius, self.max_radius),
+        ];
+        for &(name, min, max) in ranges.iter() {
+            if min > max {
+                return Err(format!(
+                    "balls.min_{} ({}) is greater than balls.max_{} ({})",
+                    name, min, name, max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DebugConfig {
+    // None keeps the overlay's default, shown only in debug builds
+    pub overlay: Option<bool>,
+    pub toggle_key: Key,
+}
+
+impl Default for DebugConfig {
+    fn default() -> DebugConfig {
+        DebugConfig {
+            overlay: None,
+            toggle_key: Key::F3,
+        }
+    }
+}
+
+// Settings shared by every binary. The defaults come from the binary itself, then the config
+// file (`config.toml` or the one given with `--config <path>`) and finally the command line
+// options (`--width`, `--height`, `--balls`, `--seed`, `--level`, `--strict-assets`,
+// `--hot-reload`) override them. When replaying (`--replay <path>`) the seed and the screen
+// size are taken from the recording.
+#[derive(Clone, Debug)]
+pub struct GameConfig {
+    pub window: WindowConfig,
+    pub balls: BallConfig,
+    pub debug: DebugConfig,
+    pub seed: Option<u64>,
+    pub level: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    // Unknown sprite names are reported when the assets are loaded instead of drawing nothing
+    pub strict_assets: bool,
+    // Assets are reloaded while the game runs when their files change
+    pub hot_reload: bool,
+}
+
+#[allow(dead_code)]
+impl GameConfig {
+    pub fn new(window: &WindowConfig) -> GameConfig {
+        GameConfig {
+            window: window.clone(),
+            balls: BallConfig::default(),
+            debug: DebugConfig::default(),
+            seed: None,
+            level: None,
+            record: None,
+            replay: None,
+            strict_assets: false,
+            hot_reload: false,
+        }
+    }
+
+    pub fn load(window: &WindowConfig) -> GameConfig {
+        let args: Vec<String> = env::args().collect();
+        let mut config = GameConfig::new(window);
+
+        match option::<String>(&args, "--config") {
+            Some(path) => config.apply_file(&path),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                config.apply_file(DEFAULT_CONFIG_FILE)
+            }
+            None => {}
+        }
+        config.apply_args(&args);
+        config.apply_recording();
+
+        config
+    }
+
+    pub fn window_settings(&self) -> WindowSettings {
+        WindowSettings::new(
+            self.window.title.clone(),
+            [self.window.width, self.window.height],
+        )
+        .graphics_api(opengl_version(&self.window.opengl))
+        .exit_on_esc(self.window.exit_on_esc)
+        .resizable(self.window.resizable)
+    }
+
+    // Seeded when `seed` is set, so the same config always produces the same game
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    fn apply_file(&mut self, path: &str) {
+        let content = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Cannot read config file {}: {}", path, e));
+        let file: ConfigFile = toml::from_str(&content)
+            .unwrap_or_else(|e| panic!("Invalid config file {}: {}", path, e));
+
+        let window = file.window;
+        set(&mut self.window.title, window.title);
+        set(&mut self.window.width, window.width);
+        set(&mut self.window.height, window.height);
+        set(&mut self.window.opengl, window.opengl);
+        set(&mut self.window.resizable, window.resizable);
+        set(&mut self.window.exit_on_esc, window.exit_on_esc);
+
+        let balls = file.balls;
+        set(&mut self.balls.count, balls.count);
+        set(&mut self.balls.min_velocity, balls.min_velocity);
+        set(&mut self.balls.max_velocity, balls.max_velocity);
+        set(&mut self.balls.min_radius, balls.min_radius);
+        set(&mut self.balls.max_radius, balls.max_radius);
+        if let Err(e) = self.balls.validate() {
+            panic!("Invalid config file {}: {}", path, e);
+        }
+
+        let debug = file.debug;
+        if debug.overlay.is_some() {
+            self.debug.overlay = debug.overlay;
+        }
+        set(&mut self.debug.toggle_key, debug.toggle_key);
+
+        if file.seed.is_some() {
+            self.seed = file.seed;
+        }
+        if file.level.is_some() {
+            self.level = file.level;
+        }
+        set(&mut self.strict_assets, file.strict_assets);
+        set(&mut self.hot_reload, file.hot_reload);
+    }
+
+    fn apply_args(&mut self, args: &[String]) {
+        set(&mut self.window.width, option(args, "--width"));
+        set(&mut self.window.height, option(args, "--height"));
+        set(&mut self.balls.count, option(args, "--balls"));
+        if let Some(seed) = option(args, "--seed") {
+            self.seed = Some(seed);
+        }
+        if let Some(level) = option(args, "--level") {
+            self.level = Some(level);
+        }
+        if flag(args, "--strict-assets") {
+            self.strict_assets = true;
+        }
+        if flag(args, "--hot-reload") {
+            self.hot_reload = true;
+        }
+        self.record = option(args, "--record");
+        self.replay = option(args, "--replay");
+    }
+
+    // Replays the recording at `path`, the same as `--replay <path>`
+    pub fn set_replay(&mut self, path: &str) -> &mut GameConfig {
+        self.replay = Some(path.to_string());
+        self.apply_recording();
+        self
+    }
+
+    // A recording can only be replayed with the seed it was made with
+    fn apply_recording(&mut self) {
+        if let Some(ref path) = self.replay {
+            let header = Recording::load_header(path);
+            self.seed = Some(header.seed);
+            self.window.width = header.width;
+            self.window.height = header.height;
+        } else if self.record.is_some() && self.seed.is_none() {
+            self.seed = Some(rand::random());
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ConfigFile {
+    window: WindowSection,
+    balls: BallSection,
+    debug: DebugSection,
+    seed: Option<u64>,
+    level: Option<String>,
+    strict_assets: Option<bool>,
+    hot_reload: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct WindowSection {
+    title: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    opengl: Option<String>,
+    resizable: Option<bool>,
+    exit_on_esc: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct BallSection {
+    count: Option<u32>,
+    min_velocity: Option<f64>,
+    max_velocity: Option<f64>,
+    min_radius: Option<f64>,
+    max_radius: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DebugSection {
+    overlay: Option<bool>,
+    // The key's name, e.g. "F3" or "Backquote"
+    toggle_key: Option<Key>,
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+// Value following `name` on the command line, e.g. `--balls 500`
+fn option<T>(args: &[String], name: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    args.iter()
+        .position(|arg| arg == name)
+        .map(|i| match args.get(i + 1) {
+            Some(value) => value
+                .parse()
+                .unwrap_or_else(|e| panic!("Invalid value for {}: {} ({})", name, value, e)),
+            None => panic!("Missing value for {}", name),
+        })
+}
+
+fn flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+fn opengl_version(version: &str) -> OpenGL {
+    match version {
+        "2.0" => OpenGL::V2_0,
+        "2.1" => OpenGL::V2_1,
+        "3.0" => OpenGL::V3_0,
+        "3.1" => OpenGL::V3_1,
+        "3.2" => OpenGL::V3_2,
+        "3.3" => OpenGL::V3_3,
+        "4.0" => OpenGL::V4_0,
+        "4.1" => OpenGL::V4_1,
+        "4.2" => OpenGL::V4_2,
+        "4.3" => OpenGL::V4_3,
+        "4.4" => OpenGL::V4_4,
+        "4.5" => OpenGL::V4_5,
+        _ => panic!("Unsupported OpenGL version: {}", version),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balls(min_velocity: f64, max_velocity: f64, min_radius: f64, max_radius: f64) -> BallConfig {
+        BallConfig {
+            count: 10,
+            min_velocity,
+            max_velocity,
+            min_radius,
+            max_radius,
+        }
+    }
+
+    #[test]
+    fn default_balls_are_valid() {
+        assert_eq!(BallConfig::default().validate(), Ok(()));
+        assert_eq!(balls(1.0, 1.0, 5.0, 5.0).validate(), Ok(()));
+    }
+
+    #[test]
+    fn empty_ranges_name_the_field() {
+        assert_eq!(
+            balls(1000.0, 500.0, 10.0, 20.0).validate(),
+            Err("balls.min_velocity (1000) is greater than balls.max_velocity (500)".to_string())
+        );
+        assert_eq!(
+            balls(500.0, 1000.0, 20.0, 10.0).validate(),
+            Err("balls.min_radius (20) is greater than balls.max_radius (10)".to_string())
+        );
+    }
+
+    #[test]
+    fn values_must_be_positive() {
+        assert_eq!(
+            balls(0.0, 1000.0, 10.0, 20.0).validate(),
+            Err("balls.min_velocity must be positive, got 0".to_string())
+        );
+        assert_eq!(
+            balls(500.0, 1000.0, -10.0, 20.0).validate(),
+            Err("balls.min_radius must be positive, got -10".to_string())
+        );
+        assert_eq!(
+            balls(500.0, 1000.0, 10.0, f64::NAN).validate(),
+            Err("balls.max_radius must be positive, got NaN".to_string())
+        );
+    }
+}
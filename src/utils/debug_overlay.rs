@@ -0,0 +1,219 @@
+use piston::Button::Keyboard;
+use piston::{ButtonArgs, ButtonState, Key};
+use piston_window::{Context, Transformed};
+use std::fmt::Display;
+use utils::canvas::Canvas;
+use utils::config::DebugConfig;
+use utils::fps_counter::FpsCounter;
+
+const HISTORY_SIZE: usize = 120;
+const FONT_SIZE: u32 = 12;
+const LINE_HEIGHT: f64 = 16.0;
+const PADDING: f64 = 5.0;
+const BAR_WIDTH: f64 = 2.0;
+const GRAPH_HEIGHT: f64 = 60.0;
+const GRAPH_MAX_FRAME_TIME: f64 = 2.0 / 60.0; // 2 frames @ 60 FPS
+const TARGET_FRAME_TIME: f64 = 1.0 / 60.0;
+
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const GOOD_FRAME_COLOR: [f32; 4] = [0.2, 0.8, 0.2, 1.0];
+const SLOW_FRAME_COLOR: [f32; 4] = [0.9, 0.2, 0.2, 1.0];
+const TARGET_LINE_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 0.6];
+
+// Key / value lines shown by the debug overlay, filled by `GameWindow` and `Scene::debug_info`
+pub struct DebugInfo {
+    lines: Vec<(String, String)>,
+}
+
+#[allow(dead_code)]
+impl DebugInfo {
+    pub fn new() -> DebugInfo {
+        DebugInfo { lines: Vec::new() }
+    }
+
+    pub fn add<V: Display>(&mut self, key: &str, value: V) {
+        self.lines.push((key.to_string(), value.to_string()));
+    }
+
+    pub fn lines(&self) -> &[(String, String)] {
+        &self.lines
+    }
+}
+
+impl Default for DebugInfo {
+    fn default() -> DebugInfo {
+        DebugInfo::new()
+    }
+}
+
+pub struct DebugOverlay {
+    enabled: bool,
+    toggle_key: Key,
+    update_time: f64,
+    render_time: f64,
+}
+
+#[allow(dead_code)]
+impl DebugOverlay {
+    // Shown by default only in debug builds
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            enabled: cfg!(debug_assertions),
+            toggle_key: Key::F3,
+            update_time: 0.0,
+            render_time: 0.0,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) -> &mut DebugOverlay {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn set_toggle_key(&mut self, key: Key) -> &mut DebugOverlay {
+        self.toggle_key = key;
+        self
+    }
+
+    // Applies the `[debug]` section of the config file
+    pub fn configure(&mut self, config: &DebugConfig) -> &mut DebugOverlay {
+        if let Some(enabled) = config.overlay {
+            self.set_enabled(enabled);
+        }
+        self.set_toggle_key(config.toggle_key)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Returns true if the event was used to toggle the overlay
+    pub fn on_button_event(&mut self, args: ButtonArgs) -> bool {
+        if args.button != Keyboard(self.toggle_key) {
+            return false;
+        }
+        if args.state == ButtonState::Press {
+            self.enabled = !self.enabled;
+        }
+        true
+    }
+
+    // The time spent with updating and rendering the scene during the frame, in seconds
+    pub fn record_frame(&mut self, update_time: f64, render_time: f64) {
+        self.update_time = update_time;
+        self.render_time = render_time;
+    }
+
+    // The frame time graph shows the latest frames the `FpsCounter` measured
+    pub fn render(
+        &self,
+        c: Context,
+        g: &mut dyn Canvas,
+        info: &DebugInfo,
+        fps_counter: &FpsCounter,
+    ) {
+        let line_count = info.lines().len() + 2;
+        let text_height = line_count as f64 * LINE_HEIGHT;
+        let width = HISTORY_SIZE as f64 * BAR_WIDTH;
+        g.rectangle(
+            BACKGROUND_COLOR,
+            [
+                0.0,
+                0.0,
+                width + 2.0 * PADDING,
+                text_height + GRAPH_HEIGHT + 3.0 * PADDING,
+            ],
+            c.transform,
+        );
+
+        let mut lines = vec![
+            format!("update: {:.2} ms", self.update_time * 1000.0),
+            format!("render: {:.2} ms", self.render_time * 1000.0),
+        ];
+        lines.extend(
+            info.lines()
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value)),
+        );
+        for (i, line) in lines.iter().enumerate() {
+            let y = PADDING + (i + 1) as f64 * LINE_HEIGHT - 4.0;
+            g.text(
+                TEXT_COLOR,
+                FONT_SIZE,
+                line.as_str(),
+                c.transform.trans(PADDING, y),
+            );
+        }
+
+        let graph_bottom = text_height + GRAPH_HEIGHT + 2.0 * PADDING;
+        for (i, frame_time) in fps_counter.recent_frame_times(HISTORY_SIZE).enumerate() {
+            let height = (frame_time / GRAPH_MAX_FRAME_TIME).min(1.0) * GRAPH_HEIGHT;
+            let color = if frame_time > TARGET_FRAME_TIME * 1.05 {
+                SLOW_FRAME_COLOR
+            } else {
+                GOOD_FRAME_COLOR
+            };
+            g.rectangle(
+                color,
+                [
+                    PADDING + i as f64 * BAR_WIDTH,
+                    graph_bottom - height,
+                    BAR_WIDTH,
+                    height,
+                ],
+                c.transform,
+            );
+        }
+
+        let target_y = graph_bottom - TARGET_FRAME_TIME / GRAPH_MAX_FRAME_TIME * GRAPH_HEIGHT;
+        g.rectangle(
+            TARGET_LINE_COLOR,
+            [PADDING, target_y, width, 1.0],
+            c.transform,
+        );
+    }
+}
+
+impl Default for DebugOverlay {
+    fn default() -> DebugOverlay {
+        DebugOverlay::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(overlay: &mut DebugOverlay, key: Key, state: ButtonState) -> bool {
+        overlay.on_button_event(ButtonArgs {
+            state,
+            button: Keyboard(key),
+            scancode: None,
+        })
+    }
+
+    #[test]
+    fn configured_toggle_key_toggles_the_overlay() {
+        let mut overlay = DebugOverlay::new();
+        overlay.configure(&DebugConfig {
+            overlay: Some(false),
+            toggle_key: Key::F1,
+        });
+        assert!(!overlay.is_enabled());
+
+        assert!(!event(&mut overlay, Key::F3, ButtonState::Press));
+        assert!(!overlay.is_enabled());
+
+        assert!(event(&mut overlay, Key::F1, ButtonState::Press));
+        assert!(event(&mut overlay, Key::F1, ButtonState::Release));
+        assert!(overlay.is_enabled());
+    }
+
+    #[test]
+    fn unset_overlay_keeps_the_default() {
+        let mut overlay = DebugOverlay::new();
+        overlay.configure(&DebugConfig::default());
+        assert_eq!(overlay.is_enabled(), cfg!(debug_assertions));
+    }
+}
@@ -0,0 +1,346 @@
+use piston_window::Context;
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use utils::canvas::Canvas;
+use utils::entity::{Renderable, Updatable};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+pub struct Components<T> {
+    slots: Vec<Option<(Entity, T)>>,
+}
+
+#[allow(dead_code)]
+impl<T> Components<T> {
+    fn new() -> Components<T> {
+        Components { slots: Vec::new() }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index as usize) {
+            Some(Some((e, component))) if *e == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index as usize) {
+            Some(Some((e, component))) if *e == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|(e, component)| (*e, component)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.as_mut().map(|(e, component)| (*e, component)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert(&mut self, entity: Entity, component: T) {
+        let index = entity.index as usize;
+        if self.slots.len() <= index {
+            self.slots.resize_with(index + 1, || None);
+        }
+        self.slots[index] = Some((entity, component));
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        self.get(entity)?;
+        self.slots[entity.index as usize]
+            .take()
+            .map(|(_, component)| component)
+    }
+}
+
+trait ComponentStorage {
+    fn remove_entity(&self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: 'static> ComponentStorage for RefCell<Components<T>> {
+    fn remove_entity(&self, entity: Entity) {
+        self.borrow_mut().remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Components of the same type are stored together and borrowed as a whole, so a system can
+// read one component type while it writes another. Borrowing the same type mutably twice
+// at the same time panics, just like a `RefCell` does.
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    components: HashMap<TypeId, Box<dyn ComponentStorage>>,
+    resources: HashMap<TypeId, Box<dyn Any>>,
+}
+
+#[allow(dead_code)]
+impl World {
+    pub fn new() -> World {
+        World {
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free: Vec::new(),
+            components: HashMap::new(),
+            resources: HashMap::new(),
+        }
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        if let Some(index) = self.free.pop() {
+            self.alive[index as usize] = true;
+            Entity {
+                index,
+                generation: self.generations[index as usize],
+            }
+        } else {
+            self.generations.push(0);
+            self.alive.push(true);
+            Entity {
+                index: self.generations.len() as u32 - 1,
+                generation: 0,
+            }
+        }
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        self.components
+            .values()
+            .for_each(|storage| storage.remove_entity(entity));
+        let index = entity.index as usize;
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.free.push(entity.index);
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+        index < self.alive.len()
+            && self.alive[index]
+            && self.generations[index] == entity.generation
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.alive.iter().filter(|alive| **alive).count()
+    }
+
+    pub fn register<T: 'static>(&mut self) {
+        self.components
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(Components::<T>::new())));
+    }
+
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        self.register::<T>();
+        self.write::<T>().insert(entity, component);
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storage::<T>()
+            .and_then(|storage| storage.borrow_mut().remove(entity))
+    }
+
+    // Panics if no component of type `T` has been registered or inserted yet
+    pub fn read<T: 'static>(&self) -> Ref<'_, Components<T>> {
+        self.storage::<T>()
+            .expect("Component type is not registered")
+            .borrow()
+    }
+
+    pub fn write<T: 'static>(&self) -> RefMut<'_, Components<T>> {
+        self.storage::<T>()
+            .expect("Component type is not registered")
+            .borrow_mut()
+    }
+
+    pub fn insert_resource<R: 'static>(&mut self, resource: R) {
+        self.resources
+            .insert(TypeId::of::<R>(), Box::new(RefCell::new(resource)));
+    }
+
+    // Panics if the resource hasn't been inserted
+    pub fn resource<R: 'static>(&self) -> Ref<'_, R> {
+        self.resource_cell::<R>()
+            .expect("Resource is missing")
+            .borrow()
+    }
+
+    pub fn resource_mut<R: 'static>(&self) -> RefMut<'_, R> {
+        self.resource_cell::<R>()
+            .expect("Resource is missing")
+            .borrow_mut()
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&RefCell<Components<T>>> {
+        self.components
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any().downcast_ref::<RefCell<Components<T>>>())
+    }
+
+    fn resource_cell<R: 'static>(&self) -> Option<&RefCell<R>> {
+        self.resources
+            .get(&TypeId::of::<R>())
+            .and_then(|resource| resource.downcast_ref::<RefCell<R>>())
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
+    }
+}
+
+// Commonly used resources
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DeltaTime(pub f64);
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ScreenSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+pub trait System {
+    fn run(&mut self, world: &World);
+}
+
+pub trait RenderSystem {
+    fn render(&self, world: &World, c: Context, g: &mut dyn Canvas, alpha: f64);
+}
+
+// Systems run in the order they were added
+pub struct Schedule {
+    systems: Vec<Box<dyn System>>,
+    render_systems: Vec<Box<dyn RenderSystem>>,
+}
+
+#[allow(dead_code)]
+impl Schedule {
+    pub fn new() -> Schedule {
+        Schedule {
+            systems: Vec::new(),
+            render_systems: Vec::new(),
+        }
+    }
+
+    pub fn add_system<S: System + 'static>(&mut self, system: S) -> &mut Schedule {
+        self.systems.push(Box::new(system));
+        self
+    }
+
+    pub fn add_render_system<S: RenderSystem + 'static>(&mut self, system: S) -> &mut Schedule {
+        self.render_systems.push(Box::new(system));
+        self
+    }
+
+    pub fn run(&mut self, world: &World) {
+        self.systems.iter_mut().for_each(|system| system.run(world));
+    }
+
+    pub fn render(&self, world: &World, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        self.render_systems
+            .iter()
+            .for_each(|system| system.render(world, c, g, alpha));
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Schedule {
+        Schedule::new()
+    }
+}
+
+// Updates every `T` component with the same arguments, built from the world once per run
+pub struct UpdatableSystem<T, F> {
+    args: F,
+    component: PhantomData<T>,
+}
+
+#[allow(dead_code)]
+impl<T, F> UpdatableSystem<T, F>
+where
+    T: Updatable + 'static,
+    F: FnMut(&World) -> T::Args,
+{
+    pub fn new(args: F) -> UpdatableSystem<T, F> {
+        UpdatableSystem {
+            args,
+            component: PhantomData,
+        }
+    }
+}
+
+impl<T, F> System for UpdatableSystem<T, F>
+where
+    T: Updatable + 'static,
+    F: FnMut(&World) -> T::Args,
+{
+    fn run(&mut self, world: &World) {
+        let args = (self.args)(world);
+        world
+            .write::<T>()
+            .iter_mut()
+            .for_each(|(_, component)| component.update(&args));
+    }
+}
+
+// Renders every `T` component
+pub struct RenderableSystem<T> {
+    component: PhantomData<T>,
+}
+
+#[allow(dead_code)]
+impl<T: Renderable + 'static> RenderableSystem<T> {
+    pub fn new() -> RenderableSystem<T> {
+        RenderableSystem {
+            component: PhantomData,
+        }
+    }
+}
+
+impl<T: Renderable + 'static> RenderSystem for RenderableSystem<T> {
+    fn render(&self, world: &World, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        world
+            .read::<T>()
+            .iter()
+            .for_each(|(_, component)| component.render(c, g, alpha));
+    }
+}
+
+impl<T: Renderable + 'static> Default for RenderableSystem<T> {
+    fn default() -> RenderableSystem<T> {
+        RenderableSystem::new()
+    }
+}
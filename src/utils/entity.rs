@@ -1,11 +1,13 @@
-use piston_window::{Context, G2d};
+use piston_window::Context;
+use utils::canvas::Canvas;
 
 pub trait Renderable {
-    fn render(self, c: Context, g: &mut G2d);
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64);
 }
 
 pub trait Updatable {
     type Args;
 
-    fn update(&mut self, args: Self::Args);
+    // Borrowed, so a system can hand the same args to every entity it updates
+    fn update(&mut self, args: &Self::Args);
 }
@@ -0,0 +1,139 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+type Subscriber<E> = Box<dyn FnMut(&E)>;
+
+// Entities publish events through an `EventSender` handed to them by the owner of the bus,
+// then the owner dispatches the queued events to the subscribers at the end of the tick
+pub struct EventBus<E> {
+    queue: Rc<RefCell<Vec<E>>>,
+    subscribers: Vec<Subscriber<E>>,
+}
+
+#[allow(dead_code)]
+impl<E> EventBus<E> {
+    pub fn new() -> EventBus<E> {
+        EventBus {
+            queue: Rc::new(RefCell::new(Vec::new())),
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn sender(&self) -> EventSender<E> {
+        EventSender {
+            queue: self.queue.clone(),
+        }
+    }
+
+    pub fn publish(&self, event: E) {
+        self.queue.borrow_mut().push(event);
+    }
+
+    pub fn subscribe<F: FnMut(&E) + 'static>(&mut self, subscriber: F) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    // Delivers the events queued so far to all subscribers and returns them, so the owner of
+    // the bus can react to them too. Events published meanwhile, e.g. by a subscriber, stay
+    // queued for the next call, so a subscriber answering its own events can't loop forever.
+    pub fn dispatch(&mut self) -> Vec<E> {
+        let events: Vec<E> = self.queue.borrow_mut().drain(..).collect();
+        for event in events.iter() {
+            self.subscribers
+                .iter_mut()
+                .for_each(|subscriber| subscriber(event));
+        }
+        events
+    }
+}
+
+impl<E> Default for EventBus<E> {
+    fn default() -> EventBus<E> {
+        EventBus::new()
+    }
+}
+
+pub struct EventSender<E> {
+    queue: Rc<RefCell<Vec<E>>>,
+}
+
+#[allow(dead_code)]
+impl<E> EventSender<E> {
+    pub fn publish(&self, event: E) {
+        self.queue.borrow_mut().push(event);
+    }
+}
+
+impl<E> Clone for EventSender<E> {
+    fn clone(&self) -> EventSender<E> {
+        EventSender {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<E> fmt::Debug for EventSender<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EventSender({} queued)", self.queue.borrow().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records what each subscriber received, tagged with the subscriber
+    fn log_subscriber(bus: &mut EventBus<u32>, log: &Rc<RefCell<Vec<(char, u32)>>>, name: char) {
+        let log = log.clone();
+        bus.subscribe(move |event| log.borrow_mut().push((name, *event)));
+    }
+
+    #[test]
+    fn events_are_delivered_in_order_to_every_subscriber() {
+        let mut bus = EventBus::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        log_subscriber(&mut bus, &log, 'a');
+        log_subscriber(&mut bus, &log, 'b');
+
+        let sender = bus.sender();
+        bus.publish(1);
+        sender.publish(2);
+        bus.publish(3);
+        assert!(log.borrow().is_empty());
+
+        assert_eq!(bus.dispatch(), vec![1, 2, 3]);
+        assert_eq!(
+            *log.borrow(),
+            vec![('a', 1), ('b', 1), ('a', 2), ('b', 2), ('a', 3), ('b', 3)]
+        );
+        assert!(bus.dispatch().is_empty());
+    }
+
+    #[test]
+    fn events_published_during_the_tick_are_dispatched_at_its_end() {
+        let mut bus = EventBus::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        log_subscriber(&mut bus, &log, 'a');
+        let sender = bus.sender();
+
+        sender.publish(1);
+        assert_eq!(bus.dispatch(), vec![1]);
+        sender.publish(2);
+        assert_eq!(bus.dispatch(), vec![2]);
+        assert_eq!(*log.borrow(), vec![('a', 1), ('a', 2)]);
+    }
+
+    #[test]
+    fn events_published_by_subscribers_wait_for_the_next_dispatch() {
+        let mut bus = EventBus::new();
+        let sender = bus.sender();
+        // answers every event with the next one, forever
+        bus.subscribe(move |event| sender.publish(event + 1));
+
+        bus.publish(1);
+        assert_eq!(bus.dispatch(), vec![1]);
+        assert_eq!(bus.dispatch(), vec![2]);
+        assert_eq!(bus.dispatch(), vec![3]);
+    }
+}
@@ -1,42 +1,216 @@
-use std::time::SystemTime;
+use std::collections::VecDeque;
 
+const DEFAULT_WINDOW_SIZE: usize = 1000;
+
+// Frame times in seconds over the counter's rolling window
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub min: f64,
+    pub max: f64,
+    pub average: f64,
+    // Frame time only the slowest 1% / 0.1% of the frames reached
+    pub low_1: f64,
+    pub low_01: f64,
+}
+
+// Counts rendered frames and update ticks separately, since with a fixed timestep the
+// number of updates says nothing about how smooth the game actually looks
 pub struct FpsCounter {
-    last_update: SystemTime,
+    last_update: f64,
     frame_ctr: u32,
+    update_ctr: u32,
     pub fps: u32,
+    pub ups: u32,
+    pub stats: FrameStats,
     update_interval: f64,
+    frame_times: VecDeque<f64>,
+    window_size: usize,
+    last_frame: Option<f64>,
 }
 
 #[allow(dead_code)]
 impl FpsCounter {
     pub fn new(update_interval: f64) -> FpsCounter {
         FpsCounter {
-            last_update: SystemTime::now(),
+            last_update: 0.0,
             frame_ctr: 0,
+            update_ctr: 0,
             fps: 0,
+            ups: 0,
+            stats: FrameStats::default(),
             update_interval,
+            frame_times: VecDeque::with_capacity(DEFAULT_WINDOW_SIZE),
+            window_size: DEFAULT_WINDOW_SIZE,
+            last_frame: None,
         }
     }
 
     pub fn default() -> FpsCounter {
-        FpsCounter {
-            last_update: SystemTime::now(),
-            frame_ctr: 0,
-            fps: 0,
-            update_interval: 1.0,
+        FpsCounter::new(1.0)
+    }
+
+    // Number of frames the min / max / average and low statistics are calculated over
+    pub fn set_window_size(&mut self, window_size: usize) -> &mut FpsCounter {
+        self.window_size = window_size.max(1);
+        while self.frame_times.len() > self.window_size {
+            self.frame_times.pop_front();
         }
+        self
     }
 
-    pub fn on_update(&mut self) {
+    // `now` is the current time in seconds, read from a `Clock`
+    pub fn on_render(&mut self, now: f64) {
         self.frame_ctr += 1;
-        let duration = SystemTime::now()
-            .duration_since(self.last_update)
-            .unwrap()
-            .as_secs_f64();
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == self.window_size {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(now - last_frame);
+        }
+        self.last_frame = Some(now);
+        self.refresh(now);
+    }
+
+    // The times of the last `count` frames, oldest first. At most the window size is kept.
+    pub fn recent_frame_times(&self, count: usize) -> impl Iterator<Item = f64> + '_ {
+        let skip = self.frame_times.len().saturating_sub(count);
+        self.frame_times.iter().skip(skip).cloned()
+    }
+
+    pub fn on_update(&mut self, now: f64) {
+        self.update_ctr += 1;
+        self.refresh(now);
+    }
+
+    fn refresh(&mut self, now: f64) {
+        let duration = now - self.last_update;
         if duration >= self.update_interval {
             self.fps = (self.frame_ctr as f64 / duration) as u32;
+            self.ups = (self.update_ctr as f64 / duration) as u32;
+            self.stats = self.frame_stats();
             self.frame_ctr = 0;
-            self.last_update = SystemTime::now();
+            self.update_ctr = 0;
+            self.last_update = now;
+        }
+    }
+
+    fn frame_stats(&self) -> FrameStats {
+        if self.frame_times.is_empty() {
+            return FrameStats::default();
+        }
+
+        let mut sorted: Vec<f64> = self.frame_times.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let percentile = |p: f64| {
+            let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+            sorted[index]
+        };
+
+        FrameStats {
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            average: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            low_1: percentile(0.99),
+            low_01: percentile(0.999),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::clock::{Clock, ManualClock};
+
+    const FAST: f64 = 1.0 / 64.0;
+    const SLOW: f64 = 1.0 / 16.0;
+    const HITCH: f64 = 1.0 / 4.0;
+
+    // Renders a frame after each of the frame times, then lets the stats refresh
+    fn measure(counter: &mut FpsCounter, clock: &ManualClock, frame_times: &[f64]) {
+        counter.on_render(clock.now());
+        for frame_time in frame_times {
+            clock.advance(*frame_time);
+            counter.on_render(clock.now());
+        }
+        clock.advance(counter.update_interval);
+        counter.on_update(clock.now());
+    }
+
+    fn frames(count: usize, frame_time: f64) -> Vec<f64> {
+        vec![frame_time; count]
+    }
+
+    #[test]
+    fn counts_frames_and_updates_per_second() {
+        let clock = ManualClock::new();
+        let mut counter = FpsCounter::new(1.0);
+        for _ in 0..64 {
+            counter.on_update(clock.now());
+            counter.on_update(clock.now());
+            counter.on_render(clock.now());
+            clock.advance(FAST);
         }
+        counter.refresh(clock.now());
+
+        assert_eq!(counter.fps, 64);
+        assert_eq!(counter.ups, 128);
+    }
+
+    #[test]
+    fn reports_the_slowest_frames_as_lows() {
+        let clock = ManualClock::new();
+        let mut counter = FpsCounter::new(1.0);
+        // the 1% lows start at the 990th of the 1000 sorted frames, the 0.1% lows at the 999th
+        let mut frame_times = frames(988, FAST);
+        frame_times.extend(frames(10, SLOW));
+        frame_times.extend(frames(2, HITCH));
+        measure(&mut counter, &clock, &frame_times);
+
+        let stats = counter.stats;
+        assert_eq!(stats.min, FAST);
+        assert_eq!(stats.max, HITCH);
+        assert_eq!(stats.low_1, SLOW);
+        assert_eq!(stats.low_01, HITCH);
+        let average = (988.0 * FAST + 10.0 * SLOW + 2.0 * HITCH) / 1000.0;
+        assert!((stats.average - average).abs() < 1e-12);
+    }
+
+    #[test]
+    fn forgets_frames_outside_the_window() {
+        let clock = ManualClock::new();
+        let mut counter = FpsCounter::new(1.0);
+        counter.set_window_size(10);
+        let mut frame_times = frames(5, HITCH);
+        frame_times.extend(frames(10, FAST));
+        measure(&mut counter, &clock, &frame_times);
+
+        assert_eq!(counter.stats.max, FAST);
+        assert_eq!(counter.recent_frame_times(100).count(), 10);
+    }
+
+    #[test]
+    fn shrinking_the_window_drops_the_oldest_frames() {
+        let clock = ManualClock::new();
+        let mut counter = FpsCounter::new(1.0);
+        let mut frame_times = frames(3, HITCH);
+        frame_times.extend(frames(3, SLOW));
+        measure(&mut counter, &clock, &frame_times);
+
+        counter.set_window_size(3);
+        assert_eq!(
+            counter.recent_frame_times(10).collect::<Vec<_>>(),
+            frames(3, SLOW)
+        );
+        assert_eq!(counter.recent_frame_times(2).count(), 2);
+    }
+
+    #[test]
+    fn no_frames_no_stats() {
+        let clock = ManualClock::new();
+        let mut counter = FpsCounter::new(1.0);
+        measure(&mut counter, &clock, &[]);
+
+        assert_eq!(counter.stats, FrameStats::default());
     }
 }
@@ -1,74 +1,203 @@
 use piston_window::*;
+use utils::asset_error::AssetError;
+use utils::assets::{Assets, Handle};
+use utils::canvas::{Canvas, G2dCanvas};
+use utils::clock::{Clock, RealClock};
+use utils::debug_overlay::{DebugInfo, DebugOverlay};
 use utils::fps_counter::FpsCounter;
 use utils::input::InputHandler;
 use utils::scene::Scene;
 
-pub struct GameWindow<T: Scene + Clone + InputHandler> {
+const DEFAULT_UPS: u32 = 60;
+const DEFAULT_MAX_FRAME_TIME: f64 = 0.25;
+pub const ASSETS_FOLDER: &str = "assets";
+const DEBUG_FONT: &str = "retro_gaming.ttf";
+const HOT_RELOAD_INTERVAL: f64 = 0.5;
+
+pub struct GameWindow<T: Scene + InputHandler> {
     window: PistonWindow,
-    glyphs: Glyphs,
+    glyphs: Handle<Glyphs>,
     fps_counter: FpsCounter,
+    debug_overlay: DebugOverlay,
     scene: T,
+    clock: Box<dyn Clock>,
+    real_clock: RealClock,
+    ups: u32,
+    max_frame_time: f64,
+    accumulator: f64,
+    last_frame: f64,
+    hot_reload: Option<Assets>,
+    last_reload_check: f64,
 }
 
 #[allow(dead_code)]
-impl<T: Scene + Clone + InputHandler> GameWindow<T> {
-    pub fn new(mut window: PistonWindow, scene: T) -> GameWindow<T> {
-        let assets = find_folder::Search::ParentsThenKids(3, 3)
-            .for_folder("assets")
-            .unwrap();
-        let glyphs = window.load_font(assets.join("retro_gaming.ttf")).unwrap();
-
-        GameWindow {
+impl<T: Scene + InputHandler> GameWindow<T> {
+    // Loads its font from a fresh `Assets`, panics if it can't
+    pub fn new(window: PistonWindow, scene: T) -> GameWindow<T> {
+        Assets::new(ASSETS_FOLDER)
+            .and_then(|mut assets| GameWindow::with_assets(window, scene, &mut assets))
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn with_assets(
+        mut window: PistonWindow,
+        scene: T,
+        assets: &mut Assets,
+    ) -> Result<GameWindow<T>, AssetError> {
+        let glyphs = assets.font(DEBUG_FONT, &mut window)?;
+
+        Ok(GameWindow {
             window,
             glyphs,
             fps_counter: FpsCounter::default(),
+            debug_overlay: DebugOverlay::new(),
             scene,
-        }
+            clock: Box::new(RealClock::new()),
+            real_clock: RealClock::new(),
+            ups: DEFAULT_UPS,
+            max_frame_time: DEFAULT_MAX_FRAME_TIME,
+            accumulator: 0.0,
+            last_frame: 0.0,
+            hot_reload: None,
+            last_reload_check: 0.0,
+        })
+    }
+
+    // The game advances according to this clock, while the FPS counter always measures real time
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) -> &mut GameWindow<T> {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    // Checks the files of the assets for changes twice a second and reloads the changed ones,
+    // see `Assets::reload_changed`. Pass the `Assets` the scene got its assets from.
+    pub fn set_hot_reload(&mut self, assets: Assets) -> &mut GameWindow<T> {
+        self.hot_reload = Some(assets);
+        self
+    }
+
+    pub fn debug_overlay(&mut self) -> &mut DebugOverlay {
+        &mut self.debug_overlay
+    }
+
+    pub fn set_ups(&mut self, ups: u32) -> &mut GameWindow<T> {
+        self.ups = ups;
+        self
+    }
+
+    // Frames taking longer than this are clamped, so a long stall (e.g. dragging the window)
+    // doesn't make the game try to catch up with an ever growing number of updates
+    pub fn set_max_frame_time(&mut self, max_frame_time: f64) -> &mut GameWindow<T> {
+        self.max_frame_time = max_frame_time;
+        self
     }
 
     pub fn game_loop(&mut self) {
+        self.last_frame = self.clock.now();
         while let Some(e) = self.window.next() {
             match e {
-                Event::Loop(l) => match l {
-                    Loop::Render(_) => {
-                        let fps = self.fps_counter.fps;
-                        let glyphs = &mut self.glyphs;
-                        let scene = self.scene.clone();
-                        self.window.draw_2d(&e, |c, g, device| {
-                            clear([1.0; 4], g);
-                            scene.render(c, g);
-
-                            let transform = c.transform.trans(5.0, 21.0);
-                            text::Text::new(16)
-                                .draw(
-                                    format!("FPS: {}", fps).as_str(),
-                                    glyphs,
-                                    &c.draw_state,
-                                    transform,
-                                    g,
-                                )
-                                .unwrap();
-
-                            glyphs.factory.encoder.flush(device);
-                        });
-                    }
-                    Loop::Update(_) => {
-                        self.scene.update();
-                        self.fps_counter.on_update();
-                    }
-                    _ => {}
-                },
+                Event::Loop(Loop::Render(_)) => {
+                    self.reload_assets();
+                    let frame_start = self.real_clock.now();
+                    let alpha = self.advance();
+                    let update_time = self.real_clock.now() - frame_start;
+                    self.fps_counter.on_render(frame_start);
+                    let fps_counter = &self.fps_counter;
+                    let glyphs = &self.glyphs;
+                    let scene = &self.scene;
+                    let debug_overlay = &mut self.debug_overlay;
+                    let real_clock = &self.real_clock;
+                    self.window.draw_2d(&e, |c, g, device| {
+                        let mut glyphs = glyphs.get_mut();
+                        {
+                            let mut canvas = G2dCanvas::new(g, &mut glyphs, &c);
+                            canvas.clear([1.0; 4]);
+                            let render_start = real_clock.now();
+                            scene.render(c, &mut canvas, alpha);
+                            let render_time = real_clock.now() - render_start;
+
+                            debug_overlay.record_frame(update_time, render_time);
+                            if debug_overlay.is_enabled() {
+                                let mut info = DebugInfo::new();
+                                add_frame_stats(&mut info, fps_counter);
+                                scene.debug_info(&mut info);
+                                debug_overlay.render(c, &mut canvas, &info, fps_counter);
+                            }
+                        }
+
+                        glyphs.factory.encoder.flush(device);
+                    });
+                }
                 Event::Input(i, _) => match i {
                     Input::Resize(args) => {
                         self.scene.on_resize(args.draw_size[0], args.draw_size[1]);
                     }
                     Input::Button(args) => {
-                        self.scene.on_button_event(args);
+                        // the overlay's toggle key doesn't reach the scene
+                        let toggled = self.debug_overlay.on_button_event(args);
+                        if !toggled {
+                            self.scene.on_button_event(args);
+                        }
                     }
+                    Input::Move(motion) => self.scene.on_move_event(motion),
+                    Input::Text(text) => self.scene.on_text_event(text),
                     _ => {}
                 },
                 _ => {}
             }
         }
     }
+
+    fn reload_assets(&mut self) {
+        let now = self.real_clock.now();
+        if now - self.last_reload_check < HOT_RELOAD_INTERVAL {
+            return;
+        }
+        self.last_reload_check = now;
+        if let Some(ref mut assets) = self.hot_reload {
+            assets.reload_changed(Some(&mut self.window));
+        }
+    }
+
+    // Runs as many fixed updates as the time elapsed since the previous frame allows and
+    // returns how far the simulation is into the next update step
+    fn advance(&mut self) -> f64 {
+        let now = self.clock.now();
+        let frame_time = now - self.last_frame;
+        self.last_frame = now;
+        self.accumulator += frame_time.min(self.max_frame_time);
+
+        let dt = 1.0 / self.ups as f64;
+        while self.accumulator >= dt {
+            self.scene.update(dt);
+            self.scene.end_tick(dt);
+            self.fps_counter.on_update(self.real_clock.now());
+            self.accumulator -= dt;
+        }
+
+        self.accumulator / dt
+    }
+}
+
+fn add_frame_stats(info: &mut DebugInfo, fps_counter: &FpsCounter) {
+    let stats = fps_counter.stats;
+    info.add("FPS", fps_counter.fps);
+    info.add("UPS", fps_counter.ups);
+    info.add(
+        "frame min / avg / max",
+        format!(
+            "{:.2} / {:.2} / {:.2} ms",
+            stats.min * 1000.0,
+            stats.average * 1000.0,
+            stats.max * 1000.0
+        ),
+    );
+    info.add(
+        "1% / 0.1% low",
+        format!(
+            "{:.2} / {:.2} ms",
+            stats.low_1 * 1000.0,
+            stats.low_01 * 1000.0
+        ),
+    );
 }
@@ -0,0 +1,170 @@
+use piston::Button::{Controller, Keyboard};
+use piston::{Button, ButtonArgs, ButtonState, ControllerAxisArgs, ControllerButton, Key, Motion};
+use piston_window::Context;
+use std::env;
+use utils::canvas::{DrawCommand, RecordingCanvas};
+use utils::input::InputHandler;
+use utils::scene::Scene;
+
+const DEFAULT_UPS: u32 = 60;
+
+// Drives a scene the same way `GameWindow` does, but with simulated time and without a
+// window: input is injected by hand and frames are recorded as a list of draw commands
+pub struct HeadlessRunner<T: Scene + InputHandler> {
+    scene: T,
+    width: u32,
+    height: u32,
+    ups: u32,
+    ticks: u64,
+    canvas: RecordingCanvas,
+}
+
+#[allow(dead_code)]
+impl<T: Scene + InputHandler> HeadlessRunner<T> {
+    pub fn new(mut scene: T, width: u32, height: u32) -> HeadlessRunner<T> {
+        scene.on_resize(width, height);
+
+        HeadlessRunner {
+            scene,
+            width,
+            height,
+            ups: DEFAULT_UPS,
+            ticks: 0,
+            canvas: RecordingCanvas::new(),
+        }
+    }
+
+    pub fn set_ups(&mut self, ups: u32) -> &mut HeadlessRunner<T> {
+        self.ups = ups;
+        self
+    }
+
+    pub fn scene(&self) -> &T {
+        &self.scene
+    }
+
+    pub fn scene_mut(&mut self) -> &mut T {
+        &mut self.scene
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.ticks as f64 / self.ups as f64
+    }
+
+    pub fn button_event(&mut self, args: ButtonArgs) -> &mut HeadlessRunner<T> {
+        self.scene.on_button_event(args);
+        self
+    }
+
+    pub fn press(&mut self, button: Button) -> &mut HeadlessRunner<T> {
+        self.button_event(ButtonArgs {
+            state: ButtonState::Press,
+            button,
+            scancode: None,
+        })
+    }
+
+    pub fn release(&mut self, button: Button) -> &mut HeadlessRunner<T> {
+        self.button_event(ButtonArgs {
+            state: ButtonState::Release,
+            button,
+            scancode: None,
+        })
+    }
+
+    pub fn press_key(&mut self, key: Key) -> &mut HeadlessRunner<T> {
+        self.press(Keyboard(key))
+    }
+
+    pub fn release_key(&mut self, key: Key) -> &mut HeadlessRunner<T> {
+        self.release(Keyboard(key))
+    }
+
+    pub fn move_event(&mut self, motion: Motion) -> &mut HeadlessRunner<T> {
+        self.scene.on_move_event(motion);
+        self
+    }
+
+    pub fn move_cursor(&mut self, x: f64, y: f64) -> &mut HeadlessRunner<T> {
+        self.move_event(Motion::MouseCursor([x, y]))
+    }
+
+    pub fn text(&mut self, text: &str) -> &mut HeadlessRunner<T> {
+        self.scene.on_text_event(text.to_string());
+        self
+    }
+
+    pub fn press_controller(&mut self, id: u32, button: u8) -> &mut HeadlessRunner<T> {
+        self.press(Controller(ControllerButton { id, button }))
+    }
+
+    pub fn release_controller(&mut self, id: u32, button: u8) -> &mut HeadlessRunner<T> {
+        self.release(Controller(ControllerButton { id, button }))
+    }
+
+    pub fn controller_axis(&mut self, id: u32, axis: u8, position: f64) -> &mut HeadlessRunner<T> {
+        self.move_event(Motion::ControllerAxis(ControllerAxisArgs {
+            id,
+            axis,
+            position,
+        }))
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> &mut HeadlessRunner<T> {
+        self.width = width;
+        self.height = height;
+        self.scene.on_resize(width, height);
+        self
+    }
+
+    pub fn run(&mut self, ticks: u32) -> &mut HeadlessRunner<T> {
+        let dt = 1.0 / self.ups as f64;
+        for _ in 0..ticks {
+            self.scene.update(dt);
+            self.scene.end_tick(dt);
+            self.ticks += 1;
+        }
+        self
+    }
+
+    pub fn render(&mut self, alpha: f64) -> &[DrawCommand] {
+        let c = Context::new_abs(self.width as f64, self.height as f64);
+
+        self.canvas.commands.clear();
+        self.scene.render(c, &mut self.canvas, alpha);
+
+        &self.canvas.commands
+    }
+}
+
+// Binaries run headless when started with `--headless <ticks>`, or with just `--headless`
+// when replaying a recording
+#[allow(dead_code)]
+pub fn is_headless() -> bool {
+    env::args().any(|arg| arg == "--headless")
+}
+
+#[allow(dead_code)]
+pub fn headless_ticks() -> Option<u32> {
+    let args: Vec<String> = env::args().collect();
+    args.iter()
+        .position(|arg| arg == "--headless")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|ticks| ticks.parse().ok())
+}
+
+#[allow(dead_code)]
+pub fn run_headless<T: Scene + InputHandler>(scene: T, width: u32, height: u32, ticks: u32) {
+    let mut runner = HeadlessRunner::new(scene, width, height);
+    let commands = runner.run(ticks).render(0.0).len();
+    println!(
+        "Simulated {} ticks ({:.2}s), the last frame has {} draw commands",
+        runner.ticks(),
+        runner.elapsed(),
+        commands
+    );
+}
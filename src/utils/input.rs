@@ -1,42 +1,406 @@
-use piston::Button::Keyboard;
-use piston::{ButtonArgs, ButtonState, Key, Motion};
-use std::collections::HashSet;
+use piston::Button::{Controller, Keyboard, Mouse};
+use piston::{
+    Button, ButtonArgs, ButtonState, ControllerAxisArgs, ControllerButton, Key, Motion, MouseButton,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
+const DEFAULT_DEAD_ZONE: f64 = 0.2;
+
+// Press and release transitions are kept until `end_tick` is called after the update tick,
+// so a key tapped between two ticks is still seen as just pressed by the next one. The same
+// goes for the relative mouse motion and the scrolling, which are summed up over the tick.
 #[derive(Clone, Debug)]
 pub struct GameInput {
-    pressed_keys: HashSet<Key>,
+    pressed: HashSet<Button>,
+    just_pressed: HashSet<Button>,
+    just_released: HashSet<Button>,
+    held_time: HashMap<Button, f64>,
+    cursor: Option<[f64; 2]>,
+    cursor_delta: [f64; 2],
+    mouse_delta: [f64; 2],
+    scroll: [f64; 2],
+    controllers: BTreeSet<u32>,
+    axes: HashMap<(u32, u8), f64>,
+    dead_zone: f64,
+    axis_dead_zones: HashMap<u8, f64>,
 }
 
 #[allow(dead_code)]
 impl GameInput {
     pub fn new() -> GameInput {
         GameInput {
-            pressed_keys: HashSet::new(),
+            pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+            held_time: HashMap::new(),
+            cursor: None,
+            cursor_delta: [0.0, 0.0],
+            mouse_delta: [0.0, 0.0],
+            scroll: [0.0, 0.0],
+            controllers: BTreeSet::new(),
+            axes: HashMap::new(),
+            dead_zone: DEFAULT_DEAD_ZONE,
+            axis_dead_zones: HashMap::new(),
         }
     }
 
-    pub fn on_key_event(&mut self, event: ButtonArgs) {
-        match event.button {
-            Keyboard(key) => {
-                if event.state == ButtonState::Press {
-                    self.pressed_keys.insert(key);
-                } else {
-                    self.pressed_keys.remove(&key);
+    // Axis positions closer to the center than the dead zone are reported as 0.0, so worn
+    // sticks don't make the player drift
+    pub fn set_dead_zone(&mut self, dead_zone: f64) -> &mut GameInput {
+        self.dead_zone = dead_zone.clamp(0.0, 0.99);
+        self
+    }
+
+    // Overrides the dead zone of a single axis, e.g. for the triggers
+    pub fn set_axis_dead_zone(&mut self, axis: u8, dead_zone: f64) -> &mut GameInput {
+        self.axis_dead_zones
+            .insert(axis, dead_zone.clamp(0.0, 0.99));
+        self
+    }
+
+    // Tracks keyboard keys, mouse buttons and controller buttons
+    pub fn on_button_event(&mut self, event: ButtonArgs) {
+        let button = match event.button {
+            Keyboard(_) | Mouse(_) => event.button,
+            Controller(ControllerButton { id, .. }) => {
+                self.controllers.insert(id);
+                event.button
+            }
+            _ => return,
+        };
+        // the OS repeats the press events of held keys, those are not new presses
+        if event.state == ButtonState::Press {
+            if self.pressed.insert(button) {
+                self.just_pressed.insert(button);
+                self.held_time.insert(button, 0.0);
+            }
+        } else if self.pressed.remove(&button) {
+            self.just_released.insert(button);
+            self.held_time.remove(&button);
+        }
+    }
+
+    pub fn on_move_event(&mut self, event: Motion) {
+        match event {
+            Motion::MouseCursor(pos) => {
+                if let Some(cursor) = self.cursor {
+                    self.cursor_delta[0] += pos[0] - cursor[0];
+                    self.cursor_delta[1] += pos[1] - cursor[1];
                 }
+                self.cursor = Some(pos);
+            }
+            Motion::MouseRelative(delta) => {
+                self.mouse_delta[0] += delta[0];
+                self.mouse_delta[1] += delta[1];
+            }
+            Motion::MouseScroll(delta) => {
+                self.scroll[0] += delta[0];
+                self.scroll[1] += delta[1];
+            }
+            Motion::ControllerAxis(ControllerAxisArgs { id, axis, position }) => {
+                self.controllers.insert(id);
+                self.axes.insert((id, axis), position);
             }
             _ => {}
         }
     }
 
-    pub fn on_move_event(&mut self, _event: Motion) {
-        // todo
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    // Pressed since the end of the previous tick
+    pub fn is_just_pressed(&self, button: Button) -> bool {
+        self.just_pressed.contains(&button)
+    }
+
+    // Released since the end of the previous tick
+    pub fn is_just_released(&self, button: Button) -> bool {
+        self.just_released.contains(&button)
+    }
+
+    // Seconds of update time the button has been held for, 0.0 if it's not pressed
+    pub fn held_duration(&self, button: Button) -> f64 {
+        self.held_time.get(&button).cloned().unwrap_or(0.0)
     }
 
     pub fn is_key_pressed(&self, key: Key) -> bool {
-        self.pressed_keys.contains(&key)
+        self.is_pressed(Keyboard(key))
+    }
+
+    pub fn is_key_just_pressed(&self, key: Key) -> bool {
+        self.is_just_pressed(Keyboard(key))
+    }
+
+    pub fn is_key_just_released(&self, key: Key) -> bool {
+        self.is_just_released(Keyboard(key))
+    }
+
+    pub fn is_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.is_pressed(Mouse(button))
+    }
+
+    pub fn is_mouse_just_pressed(&self, button: MouseButton) -> bool {
+        self.is_just_pressed(Mouse(button))
+    }
+
+    pub fn is_mouse_just_released(&self, button: MouseButton) -> bool {
+        self.is_just_released(Mouse(button))
+    }
+
+    pub fn is_controller_pressed(&self, id: u32, button: u8) -> bool {
+        self.is_pressed(Controller(ControllerButton { id, button }))
+    }
+
+    // The `gamepad_*` functions check the button / axis on all controllers at once, they are
+    // what single player games usually need
+    pub fn is_gamepad_pressed(&self, button: u8) -> bool {
+        self.controllers
+            .iter()
+            .any(|id| self.is_controller_pressed(*id, button))
+    }
+
+    pub fn is_gamepad_just_pressed(&self, button: u8) -> bool {
+        self.controllers
+            .iter()
+            .any(|id| self.is_just_pressed(Controller(ControllerButton { id: *id, button })))
+    }
+
+    pub fn is_gamepad_just_released(&self, button: u8) -> bool {
+        self.controllers
+            .iter()
+            .any(|id| self.is_just_released(Controller(ControllerButton { id: *id, button })))
+    }
+
+    // Ids of the controllers that sent any event so far
+    pub fn controllers(&self) -> Vec<u32> {
+        self.controllers.iter().cloned().collect()
+    }
+
+    // Axis position between -1.0 and 1.0 with the dead zone applied and the rest of the range
+    // rescaled, so the value still changes smoothly when leaving the dead zone
+    pub fn controller_axis(&self, id: u32, axis: u8) -> f64 {
+        let position = self.axes.get(&(id, axis)).cloned().unwrap_or(0.0);
+        let dead_zone = self
+            .axis_dead_zones
+            .get(&axis)
+            .cloned()
+            .unwrap_or(self.dead_zone);
+        if position.abs() <= dead_zone {
+            return 0.0;
+        }
+        let value = (position.abs() - dead_zone) / (1.0 - dead_zone);
+        value.min(1.0) * position.signum()
+    }
+
+    // The axis position of the controller that's pushed the furthest
+    pub fn gamepad_axis(&self, axis: u8) -> f64 {
+        self.controllers
+            .iter()
+            .map(|id| self.controller_axis(*id, axis))
+            .fold(0.0, |a: f64, b: f64| if b.abs() > a.abs() { b } else { a })
+    }
+
+    // Cursor position in window coordinates (y grows downwards), None until the cursor has
+    // moved over the window
+    pub fn cursor_pos(&self) -> Option<[f64; 2]> {
+        self.cursor
+    }
+
+    // Cursor position with y growing upwards from the bottom of a `height` tall screen, the
+    // coordinate system the platformer uses
+    pub fn cursor_pos_y_up(&self, height: f64) -> Option<[f64; 2]> {
+        self.cursor.map(|pos| to_y_up(pos, height))
+    }
+
+    // How much the cursor moved during the tick
+    pub fn cursor_delta(&self) -> [f64; 2] {
+        self.cursor_delta
+    }
+
+    // Raw mouse motion during the tick, reported even when the cursor can't move any further
+    pub fn mouse_delta(&self) -> [f64; 2] {
+        self.mouse_delta
     }
+
+    pub fn scroll(&self) -> [f64; 2] {
+        self.scroll
+    }
+
+    pub fn end_tick(&mut self, dt: f64) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        self.held_time.values_mut().for_each(|time| *time += dt);
+        self.cursor_delta = [0.0, 0.0];
+        self.mouse_delta = [0.0, 0.0];
+        self.scroll = [0.0, 0.0];
+    }
+}
+
+// Converts between window coordinates and y-up coordinates, works both ways
+#[allow(dead_code)]
+pub fn to_y_up(pos: [f64; 2], height: f64) -> [f64; 2] {
+    [pos[0], height - pos[1]]
 }
 
 pub trait InputHandler {
     fn on_button_event(&mut self, args: ButtonArgs);
+
+    fn on_move_event(&mut self, _motion: Motion) {}
+
+    // Text typed by the user, already translated by the OS according to the keyboard layout
+    fn on_text_event(&mut self, _text: String) {}
+
+    // Called by `GameWindow` after every update tick, see `GameInput::end_tick`
+    fn end_tick(&mut self, _dt: f64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 0.25;
+
+    fn event(input: &mut GameInput, key: Key, state: ButtonState) {
+        input.on_button_event(ButtonArgs {
+            state,
+            button: Keyboard(key),
+            scancode: None,
+        });
+    }
+
+    fn press(input: &mut GameInput, key: Key) {
+        event(input, key, ButtonState::Press);
+    }
+
+    fn release(input: &mut GameInput, key: Key) {
+        event(input, key, ButtonState::Release);
+    }
+
+    #[test]
+    fn press_is_new_only_until_the_end_of_the_tick() {
+        let mut input = GameInput::new();
+        press(&mut input, Key::A);
+        assert!(input.is_key_pressed(Key::A));
+        assert!(input.is_key_just_pressed(Key::A));
+
+        input.end_tick(DT);
+        assert!(input.is_key_pressed(Key::A));
+        assert!(!input.is_key_just_pressed(Key::A));
+    }
+
+    #[test]
+    fn tap_within_one_tick_is_seen_by_that_tick() {
+        let mut input = GameInput::new();
+        press(&mut input, Key::A);
+        release(&mut input, Key::A);
+
+        assert!(!input.is_key_pressed(Key::A));
+        assert!(input.is_key_just_pressed(Key::A));
+        assert!(input.is_key_just_released(Key::A));
+
+        input.end_tick(DT);
+        assert!(!input.is_key_just_pressed(Key::A));
+        assert!(!input.is_key_just_released(Key::A));
+    }
+
+    #[test]
+    fn held_key_counts_the_ticks_it_was_held_for() {
+        let mut input = GameInput::new();
+        press(&mut input, Key::A);
+        assert_eq!(input.held_duration(Keyboard(Key::A)), 0.0);
+
+        for _ in 0..3 {
+            input.end_tick(DT);
+            // the OS repeating the press of a held key
+            press(&mut input, Key::A);
+            assert!(!input.is_key_just_pressed(Key::A));
+        }
+        assert_eq!(input.held_duration(Keyboard(Key::A)), 3.0 * DT);
+
+        release(&mut input, Key::A);
+        assert!(input.is_key_just_released(Key::A));
+        assert_eq!(input.held_duration(Keyboard(Key::A)), 0.0);
+    }
+
+    #[test]
+    fn release_without_press_is_ignored() {
+        let mut input = GameInput::new();
+        release(&mut input, Key::A);
+
+        assert!(!input.is_key_just_released(Key::A));
+    }
+
+    #[test]
+    fn mouse_motion_is_summed_over_the_tick() {
+        let mut input = GameInput::new();
+        input.on_move_event(Motion::MouseCursor([10.0, 10.0]));
+        input.on_move_event(Motion::MouseCursor([15.0, 12.0]));
+        input.on_move_event(Motion::MouseCursor([20.0, 20.0]));
+        assert_eq!(input.cursor_delta(), [10.0, 10.0]);
+        assert_eq!(input.cursor_pos_y_up(100.0), Some([20.0, 80.0]));
+
+        input.end_tick(DT);
+        assert_eq!(input.cursor_delta(), [0.0, 0.0]);
+        assert_eq!(input.cursor_pos(), Some([20.0, 20.0]));
+    }
+
+    fn move_axis(input: &mut GameInput, id: u32, axis: u8, position: f64) {
+        input.on_move_event(Motion::ControllerAxis(ControllerAxisArgs {
+            id,
+            axis,
+            position,
+        }));
+    }
+
+    #[test]
+    fn axis_inside_the_dead_zone_is_centered() {
+        let mut input = GameInput::new();
+        input.set_dead_zone(0.2);
+        move_axis(&mut input, 0, 0, 0.2);
+        assert_eq!(input.controller_axis(0, 0), 0.0);
+
+        move_axis(&mut input, 0, 0, -0.1);
+        assert_eq!(input.controller_axis(0, 0), 0.0);
+    }
+
+    #[test]
+    fn axis_outside_the_dead_zone_is_rescaled() {
+        let mut input = GameInput::new();
+        input.set_dead_zone(0.2);
+        move_axis(&mut input, 0, 0, 0.6);
+        assert!((input.controller_axis(0, 0) - 0.5).abs() < 1e-12);
+
+        move_axis(&mut input, 0, 0, -1.0);
+        assert_eq!(input.controller_axis(0, 0), -1.0);
+    }
+
+    #[test]
+    fn dead_zones_are_clamped_and_set_per_axis() {
+        let mut input = GameInput::new();
+        input.set_dead_zone(2.0).set_axis_dead_zone(1, -1.0);
+        move_axis(&mut input, 0, 0, 0.98);
+        move_axis(&mut input, 0, 1, 0.5);
+
+        assert_eq!(input.controller_axis(0, 0), 0.0);
+        assert_eq!(input.controller_axis(0, 1), 0.5);
+    }
+
+    #[test]
+    fn gamepad_reads_every_controller() {
+        let mut input = GameInput::new();
+        move_axis(&mut input, 0, 0, -0.5);
+        move_axis(&mut input, 3, 0, 1.0);
+        input.on_button_event(ButtonArgs {
+            state: ButtonState::Press,
+            button: Controller(ControllerButton { id: 3, button: 7 }),
+            scancode: None,
+        });
+
+        assert_eq!(input.controllers(), vec![0, 3]);
+        assert_eq!(input.gamepad_axis(0), 1.0);
+        assert!(input.is_gamepad_pressed(7));
+        assert!(input.is_gamepad_just_pressed(7));
+        assert!(!input.is_controller_pressed(0, 7));
+    }
 }
@@ -1,6 +1,21 @@
+pub mod action_map;
+pub mod asset_error;
+pub mod assets;
+pub mod atlas;
+pub mod canvas;
+pub mod clock;
+pub mod config;
+pub mod debug_overlay;
+pub mod ecs;
 pub mod entity;
+pub mod event_bus;
 pub mod fps_counter;
 pub mod game_window;
+pub mod headless;
 pub mod input;
+pub mod replay;
 pub mod scene;
+pub mod scene_manager;
+pub mod sprite_batch;
 pub mod sprite_sheet;
+pub mod text_field;
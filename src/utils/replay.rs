@@ -0,0 +1,442 @@
+use piston::{ButtonArgs, Motion};
+use piston_window::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::mem;
+use std::process;
+use utils::canvas::Canvas;
+use utils::config::GameConfig;
+use utils::debug_overlay::DebugInfo;
+use utils::headless::HeadlessRunner;
+use utils::input::InputHandler;
+use utils::scene::Scene;
+use utils::scene_manager::SceneTransition;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    Button(ButtonArgs),
+    Move(Motion),
+    Text(String),
+    Resize(u32, u32),
+}
+
+// The input events that arrived before an update tick. Fed back in the same order they
+// rebuild the exact `GameInput` state the tick saw.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TickRecord {
+    pub dt: f64,
+    pub events: Vec<RecordedEvent>,
+}
+
+// A recording is a JSON file with one line per entry: the header, the ticks, and the debug
+// info of the scene when the recording stopped, which a replay has to end up with too. The
+// events that arrived after the last tick are stored with the final state.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum RecordingLine {
+    Header(RecordingHeader),
+    Tick(TickRecord),
+    FinalState {
+        events: Vec<RecordedEvent>,
+        state: Vec<(String, String)>,
+    },
+}
+
+#[allow(dead_code)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub ticks: Vec<TickRecord>,
+    pub final_events: Vec<RecordedEvent>,
+    pub final_state: Option<Vec<(String, String)>>,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Recording {
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("Cannot open recording {}: {}", path, e));
+
+        let mut header = None;
+        let mut ticks = Vec::new();
+        let mut final_events = Vec::new();
+        let mut final_state = None;
+        for line in BufReader::new(file).lines() {
+            let line = line.unwrap_or_else(|e| panic!("Cannot read recording {}: {}", path, e));
+            let entry: RecordingLine = serde_json::from_str(&line)
+                .unwrap_or_else(|e| panic!("Invalid recording {}: {}", path, e));
+            match entry {
+                RecordingLine::Header(h) => header = Some(h),
+                RecordingLine::Tick(tick) => ticks.push(tick),
+                RecordingLine::FinalState { events, state } => {
+                    final_events = events;
+                    final_state = Some(state);
+                }
+            }
+        }
+
+        Recording {
+            header: header.unwrap_or_else(|| panic!("Recording {} has no header", path)),
+            ticks,
+            final_events,
+            final_state,
+        }
+    }
+
+    // Only reads the first line, which is all `GameConfig` needs from a recording
+    pub fn load_header(path: &str) -> RecordingHeader {
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("Cannot open recording {}: {}", path, e));
+
+        let mut line = String::new();
+        BufReader::new(file)
+            .read_line(&mut line)
+            .unwrap_or_else(|e| panic!("Cannot read recording {}: {}", path, e));
+        match serde_json::from_str(&line) {
+            Ok(RecordingLine::Header(header)) => header,
+            _ => panic!("Recording {} has no header", path),
+        }
+    }
+}
+
+enum Mode {
+    Live,
+    Recording(BufWriter<File>),
+    Replaying {
+        ticks: VecDeque<TickRecord>,
+        total: usize,
+        final_events: Vec<RecordedEvent>,
+        final_state: Option<Vec<(String, String)>>,
+        matches: Option<bool>,
+    },
+}
+
+// Wraps the top level scene. Started with `--record <file>` it writes every tick's dt and
+// input events to the file, with `--replay <file>` it ignores the live input and feeds the
+// recorded ticks to the scene instead. `GameConfig` takes the seed and the screen size from
+// the recording, so the replay runs exactly like the recorded game did.
+pub struct InputRecorder<T: Scene + InputHandler> {
+    scene: T,
+    mode: Mode,
+    pending: Vec<RecordedEvent>,
+    last_dt: Option<f64>,
+}
+
+#[allow(dead_code)]
+impl<T: Scene + InputHandler> InputRecorder<T> {
+    pub fn new(scene: T, config: &GameConfig) -> InputRecorder<T> {
+        let mode = if let Some(ref path) = config.replay {
+            let recording = Recording::load(path);
+            Mode::Replaying {
+                total: recording.ticks.len(),
+                ticks: recording.ticks.into_iter().collect(),
+                final_events: recording.final_events,
+                final_state: recording.final_state,
+                matches: None,
+            }
+        } else if let Some(ref path) = config.record {
+            let file = File::create(path)
+                .unwrap_or_else(|e| panic!("Cannot create recording {}: {}", path, e));
+            let mut writer = BufWriter::new(file);
+            let header = RecordingHeader {
+                seed: config.seed.expect("Recordings need a seed"),
+                width: config.window.width,
+                height: config.window.height,
+            };
+            write_line(&mut writer, &RecordingLine::Header(header))
+                .unwrap_or_else(|e| panic!("Cannot write recording {}: {}", path, e));
+            Mode::Recording(writer)
+        } else {
+            Mode::Live
+        };
+
+        let mut recorder = InputRecorder {
+            scene,
+            mode,
+            pending: Vec::new(),
+            last_dt: None,
+        };
+        // a recording without ticks is over before the first update
+        if recorder.is_finished() {
+            recorder.finish_replay();
+        }
+        recorder
+    }
+
+    pub fn scene(&self) -> &T {
+        &self.scene
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, Mode::Replaying { .. })
+    }
+
+    // True once every recorded tick has been replayed
+    pub fn is_finished(&self) -> bool {
+        match self.mode {
+            Mode::Replaying { ref ticks, .. } => ticks.is_empty(),
+            _ => false,
+        }
+    }
+
+    // Whether the scene ended up in the recorded final state, None while the replay is
+    // still running. Recordings without a final state always match.
+    pub fn matches_recording(&self) -> Option<bool> {
+        match self.mode {
+            Mode::Replaying { matches, .. } => matches,
+            _ => None,
+        }
+    }
+
+    // The scene's debug info at the end of the recording
+    pub fn expected_state(&self) -> Option<&[(String, String)]> {
+        match self.mode {
+            Mode::Replaying {
+                ref final_state, ..
+            } => final_state.as_ref().map(|state| state.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, event: RecordedEvent) {
+        if let Mode::Recording(_) = self.mode {
+            self.pending.push(event);
+        }
+    }
+
+    fn deliver(&mut self, event: RecordedEvent) {
+        match event {
+            RecordedEvent::Button(args) => self.scene.on_button_event(args),
+            RecordedEvent::Move(motion) => self.scene.on_move_event(motion),
+            RecordedEvent::Text(text) => self.scene.on_text_event(text),
+            RecordedEvent::Resize(width, height) => self.scene.on_resize(width, height),
+        }
+    }
+
+    fn final_state(&self) -> Vec<(String, String)> {
+        let mut info = DebugInfo::new();
+        self.scene.debug_info(&mut info);
+        info.lines().to_vec()
+    }
+
+    fn replay_tick(&mut self) {
+        let tick = match self.mode {
+            Mode::Replaying { ref mut ticks, .. } => ticks.pop_front(),
+            _ => None,
+        };
+        let tick = match tick {
+            Some(tick) => tick,
+            None => {
+                self.last_dt = None;
+                return;
+            }
+        };
+
+        for event in tick.events {
+            self.deliver(event);
+        }
+        self.scene.update(tick.dt);
+        self.last_dt = Some(tick.dt);
+    }
+
+    // Delivers the events that arrived after the last tick and compares the final state
+    fn finish_replay(&mut self) {
+        let events = match self.mode {
+            Mode::Replaying {
+                ref mut final_events,
+                ..
+            } => mem::take(final_events),
+            _ => return,
+        };
+        for event in events {
+            self.deliver(event);
+        }
+
+        let state = self.final_state();
+        if let Mode::Replaying {
+            ref final_state,
+            ref mut matches,
+            ..
+        } = self.mode
+        {
+            *matches = Some(
+                final_state
+                    .as_ref()
+                    .map_or(true, |expected| *expected == state),
+            );
+        }
+    }
+
+    fn record_tick(&mut self, dt: f64) {
+        let tick = TickRecord {
+            dt,
+            events: mem::take(&mut self.pending),
+        };
+        let failed = match self.mode {
+            Mode::Recording(ref mut writer) => {
+                write_line(writer, &RecordingLine::Tick(tick)).is_err()
+            }
+            _ => false,
+        };
+        if failed {
+            println!("Cannot write the recording, stopped recording");
+            self.mode = Mode::Live;
+        }
+    }
+}
+
+impl<T: Scene + InputHandler> Scene for InputRecorder<T> {
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        self.scene.render(c, g, alpha);
+    }
+
+    fn update(&mut self, dt: f64) {
+        if self.is_replaying() {
+            self.replay_tick();
+            return;
+        }
+        self.record_tick(dt);
+        self.scene.update(dt);
+    }
+
+    fn on_resize(&mut self, new_width: u32, new_height: u32) {
+        if self.is_replaying() {
+            return;
+        }
+        self.record(RecordedEvent::Resize(new_width, new_height));
+        self.scene.on_resize(new_width, new_height);
+    }
+
+    fn transition(&mut self) -> Option<SceneTransition> {
+        self.scene.transition()
+    }
+
+    fn is_overlay(&self) -> bool {
+        self.scene.is_overlay()
+    }
+
+    fn debug_info(&self, info: &mut DebugInfo) {
+        if let Mode::Replaying {
+            ref ticks, total, ..
+        } = self.mode
+        {
+            info.add("replay", format!("{} / {}", total - ticks.len(), total));
+        }
+        self.scene.debug_info(info);
+    }
+}
+
+impl<T: Scene + InputHandler> InputHandler for InputRecorder<T> {
+    fn on_button_event(&mut self, args: ButtonArgs) {
+        if self.is_replaying() {
+            return;
+        }
+        self.record(RecordedEvent::Button(args));
+        self.scene.on_button_event(args);
+    }
+
+    fn on_move_event(&mut self, motion: Motion) {
+        if self.is_replaying() {
+            return;
+        }
+        self.record(RecordedEvent::Move(motion));
+        self.scene.on_move_event(motion);
+    }
+
+    fn on_text_event(&mut self, text: String) {
+        if self.is_replaying() {
+            return;
+        }
+        self.record(RecordedEvent::Text(text.clone()));
+        self.scene.on_text_event(text);
+    }
+
+    // During a replay the scene's tick ends with the recorded dt
+    fn end_tick(&mut self, dt: f64) {
+        if self.is_replaying() {
+            if let Some(dt) = self.last_dt.take() {
+                self.scene.end_tick(dt);
+                if self.is_finished() {
+                    self.finish_replay();
+                }
+            }
+            return;
+        }
+        self.scene.end_tick(dt);
+    }
+}
+
+impl<T: Scene + InputHandler> Drop for InputRecorder<T> {
+    fn drop(&mut self) {
+        let line = RecordingLine::FinalState {
+            events: mem::take(&mut self.pending),
+            state: self.final_state(),
+        };
+        if let Mode::Recording(ref mut writer) = self.mode {
+            let result = write_line(writer, &line).and_then(|_| writer.flush());
+            if let Err(e) = result {
+                println!("Cannot finish the recording: {}", e);
+            }
+        }
+    }
+}
+
+fn write_line(writer: &mut BufWriter<File>, line: &RecordingLine) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, line)?;
+    writer.write_all(b"\n")
+}
+
+// Replays the recording as fast as possible and exits with an error code if the scene didn't
+// end up in the recorded state, so recordings can be used as regression tests:
+// `cargo run --bin platformer -- --replay bug.json --headless`
+#[allow(dead_code)]
+pub fn run_replay_headless<T: Scene + InputHandler>(
+    recorder: InputRecorder<T>,
+    width: u32,
+    height: u32,
+) -> ! {
+    let runner = replay_headless(recorder, width, height);
+
+    match runner.scene().matches_recording() {
+        Some(true) => {
+            println!("Replayed {} ticks, the final state matches", runner.ticks());
+            process::exit(0);
+        }
+        Some(false) => {
+            println!("Replayed {} ticks, the final state differs", runner.ticks());
+            let expected = runner.scene().expected_state().unwrap_or(&[]);
+            for (key, value) in runner.scene().final_state() {
+                match expected.iter().find(|(k, _)| *k == key) {
+                    Some((_, v)) if *v == value => {}
+                    Some((_, v)) => println!("  {}: {} (recorded: {})", key, value, v),
+                    None => println!("  {}: {} (not recorded)", key, value),
+                }
+            }
+            process::exit(1);
+        }
+        None => {
+            println!("Replayed {} ticks, nothing to compare", runner.ticks());
+            process::exit(0);
+        }
+    }
+}
+
+// Runs every recorded tick, the result is in `matches_recording`
+#[allow(dead_code)]
+pub fn replay_headless<T: Scene + InputHandler>(
+    recorder: InputRecorder<T>,
+    width: u32,
+    height: u32,
+) -> HeadlessRunner<InputRecorder<T>> {
+    let mut runner = HeadlessRunner::new(recorder, width, height);
+    while !runner.scene().is_finished() {
+        runner.run(1);
+    }
+    runner
+}
@@ -1,7 +1,25 @@
-use piston_window::{Context, G2d};
+use piston_window::Context;
+use utils::canvas::Canvas;
+use utils::debug_overlay::DebugInfo;
+use utils::scene_manager::SceneTransition;
 
 pub trait Scene {
-    fn render(self, c: Context, g: &mut G2d);
-    fn update(&mut self);
+    // `alpha` is the fraction of a fixed update step that has elapsed since the last
+    // update, used to interpolate between the previous and the current state
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64);
+    fn update(&mut self, dt: f64);
     fn on_resize(&mut self, new_width: u32, new_height: u32);
+
+    // Polled by the `SceneManager` after every update and input event
+    fn transition(&mut self) -> Option<SceneTransition> {
+        None
+    }
+
+    // Overlays are drawn on top of the scene below them instead of replacing it
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    // Lines shown in the debug overlay while it's enabled
+    fn debug_info(&self, _info: &mut DebugInfo) {}
 }
@@ -0,0 +1,139 @@
+use piston::{ButtonArgs, Motion};
+use piston_window::Context;
+use utils::canvas::Canvas;
+use utils::debug_overlay::DebugInfo;
+use utils::input::InputHandler;
+use utils::scene::Scene;
+
+pub trait StackedScene: Scene + InputHandler {}
+
+impl<T: Scene + InputHandler> StackedScene for T {}
+
+#[allow(dead_code)]
+pub enum SceneTransition {
+    Push(Box<dyn StackedScene>),
+    Pop,
+    Replace(Box<dyn StackedScene>),
+}
+
+#[allow(dead_code)]
+impl SceneTransition {
+    pub fn push<T: Scene + InputHandler + 'static>(scene: T) -> SceneTransition {
+        SceneTransition::Push(Box::new(scene))
+    }
+
+    pub fn replace<T: Scene + InputHandler + 'static>(scene: T) -> SceneTransition {
+        SceneTransition::Replace(Box::new(scene))
+    }
+}
+
+// Only the top scene is updated and receives input, the ones below it are paused until
+// it gets popped. The bottom scene is never popped, so there's always something to show.
+pub struct SceneManager {
+    stack: Vec<Box<dyn StackedScene>>,
+}
+
+#[allow(dead_code)]
+impl SceneManager {
+    pub fn new<T: Scene + InputHandler + 'static>(scene: T) -> SceneManager {
+        SceneManager {
+            stack: vec![Box::new(scene)],
+        }
+    }
+
+    pub fn push(&mut self, scene: Box<dyn StackedScene>) {
+        self.stack.push(scene);
+    }
+
+    pub fn pop(&mut self) -> Option<Box<dyn StackedScene>> {
+        if self.stack.len() > 1 {
+            self.stack.pop()
+        } else {
+            None
+        }
+    }
+
+    pub fn replace(&mut self, scene: Box<dyn StackedScene>) {
+        self.stack.pop();
+        self.stack.push(scene);
+    }
+
+    fn apply_transition(&mut self) {
+        let transition = self.stack.last_mut().and_then(|scene| scene.transition());
+
+        match transition {
+            Some(SceneTransition::Push(scene)) => self.push(scene),
+            Some(SceneTransition::Pop) => {
+                self.pop();
+            }
+            Some(SceneTransition::Replace(scene)) => self.replace(scene),
+            None => {}
+        }
+    }
+}
+
+impl Scene for SceneManager {
+    fn render(&self, c: Context, g: &mut dyn Canvas, alpha: f64) {
+        let first_visible = self
+            .stack
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+
+        // only the top scene is updated, the ones under it are frozen where they stopped
+        let top = self.stack.len() - 1;
+        self.stack[first_visible..]
+            .iter()
+            .enumerate()
+            .for_each(|(i, scene)| {
+                let alpha = if first_visible + i == top { alpha } else { 1.0 };
+                scene.render(c, g, alpha)
+            });
+    }
+
+    fn update(&mut self, dt: f64) {
+        if let Some(scene) = self.stack.last_mut() {
+            scene.update(dt);
+        }
+        self.apply_transition();
+    }
+
+    fn on_resize(&mut self, new_width: u32, new_height: u32) {
+        self.stack
+            .iter_mut()
+            .for_each(|scene| scene.on_resize(new_width, new_height));
+    }
+
+    fn debug_info(&self, info: &mut DebugInfo) {
+        info.add("scenes", self.stack.len());
+        self.stack.iter().for_each(|scene| scene.debug_info(info));
+    }
+}
+
+impl InputHandler for SceneManager {
+    fn on_button_event(&mut self, args: ButtonArgs) {
+        if let Some(scene) = self.stack.last_mut() {
+            scene.on_button_event(args);
+        }
+        self.apply_transition();
+    }
+
+    fn on_move_event(&mut self, motion: Motion) {
+        if let Some(scene) = self.stack.last_mut() {
+            scene.on_move_event(motion);
+        }
+    }
+
+    fn on_text_event(&mut self, text: String) {
+        if let Some(scene) = self.stack.last_mut() {
+            scene.on_text_event(text);
+        }
+        self.apply_transition();
+    }
+
+    fn end_tick(&mut self, dt: f64) {
+        if let Some(scene) = self.stack.last_mut() {
+            scene.end_tick(dt);
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use utils::canvas::Quad;
+
+// Sprites of one sprite sheet collected over a frame and drawn together, with a single call to
+// the canvas instead of one per sprite. Queue them with `SpriteSheet::queue` and draw them with
+// `SpriteSheet::draw_batch`, which empties the batch for the next frame.
+#[derive(Debug, Clone, Default)]
+pub struct SpriteBatch {
+    quads: Vec<Quad>,
+}
+
+#[allow(dead_code)]
+impl SpriteBatch {
+    pub fn new() -> SpriteBatch {
+        SpriteBatch { quads: Vec::new() }
+    }
+
+    pub fn push(&mut self, quad: Quad) {
+        self.quads.push(quad);
+    }
+
+    pub fn quads(&self) -> &[Quad] {
+        &self.quads
+    }
+
+    pub fn len(&self) -> usize {
+        self.quads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quads.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.quads.clear();
+    }
+}
@@ -1,84 +1,403 @@
 use piston_window::*;
-use quick_xml::de::from_reader;
-use serde::Deserialize;
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use utils::asset_error::AssetError;
+use utils::atlas::{AtlasFormat, AtlasLoader, SubTexture, TextureAtlas};
+use utils::canvas::{Canvas, Quad};
+use utils::sprite_batch::SpriteBatch;
+
+// Handle to a sprite of a `SpriteSheet`, look it up once with `SpriteSheet::sprite_id` and
+// keep it instead of the sprite's name
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteId(usize);
+
+// How `SpriteSheet::render_with` draws a sprite, the defaults draw it like `render` does.
+// Change only what's needed, e.g. `DrawParams { flip_x: true, ..DrawParams::default() }`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawParams {
+    // Mirrored in place, around the center of the frame
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // Radians, clockwise on the screen, around the anchor
+    pub rotation: f64,
+    pub scale: [f64; 2],
+    // The point of the frame that's drawn at the position, relative to the frame's size:
+    // [0.0, 0.0] is the top left corner, [0.5, 0.5] the center
+    pub anchor: [f64; 2],
+    // Multiplies the sprite's colors, white leaves them unchanged
+    pub tint: [f32; 4],
+    // Multiplies the tint's alpha, 0.0 is invisible
+    pub alpha: f32,
+}
+
+impl Default for DrawParams {
+    fn default() -> DrawParams {
+        DrawParams {
+            flip_x: false,
+            flip_y: false,
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+            anchor: [0.0, 0.0],
+            tint: [1.0, 1.0, 1.0, 1.0],
+            alpha: 1.0,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct SpriteSheet {
     atlas: TextureAtlas,
-    texture: G2dTexture,
+    index: HashMap<String, SpriteId>,
+    texture: Option<G2dTexture>,
+    strict: bool,
 }
 
 #[allow(dead_code)]
 impl SpriteSheet {
-    pub fn new(
-        assets_folder: &str,
-        sprites: &str,
-        texture_context: &mut G2dTextureContext,
-    ) -> SpriteSheet {
-        let assets = find_folder::Search::ParentsThenKids(3, 3)
-            .for_folder(assets_folder)
-            .expect("Assets folder missing");
-
-        let file = File::open(assets.join(sprites)).expect("Missing descriptor for sprites");
-        let reader = BufReader::new(file);
-
-        let atlas: TextureAtlas = from_reader(reader).expect("Invalid sprite descriptor format");
+    // Loads the descriptor at `path` with `loader`, or the one detected from the extension
+    // if None, see `AtlasFormat::detect`. Without a texture context only the descriptor is
+    // loaded, so sprites can be looked up and "drawn" into a `RecordingCanvas` without a
+    // window. Scenes get their sprite sheets from `Assets` instead of calling this.
+    pub fn load(
+        path: &Path,
+        loader: Option<&dyn AtlasLoader>,
+        texture_context: Option<&mut G2dTextureContext>,
+    ) -> Result<SpriteSheet, AssetError> {
+        let content = fs::read_to_string(path).map_err(|e| AssetError::not_found(path, e))?;
+        let atlas = match loader {
+            Some(loader) => loader.parse(path, &content)?,
+            None => AtlasFormat::detect(path, &content)
+                .ok_or_else(|| AssetError::parse(path, None, "unknown sprite sheet format"))?
+                .parse(path, &content)?,
+        };
 
-        let texture_path = assets.join(&atlas.image_path);
+        let texture_context = match texture_context {
+            Some(texture_context) => texture_context,
+            None => return Ok(SpriteSheet::from_atlas(atlas, None)),
+        };
+        // the image path is relative to the descriptor
+        let folder = path.parent().unwrap_or_else(|| Path::new(""));
+        let texture_path = folder.join(&atlas.image_path);
+        if !texture_path.is_file() {
+            return Err(AssetError::not_found(&texture_path, "no such file"));
+        }
         let texture: G2dTexture = Texture::from_path(
             texture_context,
             &texture_path,
             Flip::None,
             &TextureSettings::new(),
         )
-        .expect("Cannot load sprites as texture");
+        .map_err(|e| AssetError::parse(&texture_path, None, e))?;
+
+        Ok(SpriteSheet::from_atlas(atlas, Some(texture)))
+    }
+
+    // In strict mode a reload that removes any of the sprites fails instead of leaving blanks
+    // where they were drawn. Scenes should check the sprites they use with `find` when they
+    // are loaded, drawing an unknown sprite by name draws nothing either way.
+    pub fn set_strict(&mut self, strict: bool) -> &mut SpriteSheet {
+        self.strict = strict;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    fn from_atlas(atlas: TextureAtlas, texture: Option<G2dTexture>) -> SpriteSheet {
+        SpriteSheet {
+            index: index_atlas(&atlas),
+            atlas,
+            texture,
+            strict: false,
+        }
+    }
+
+    // Takes over the sprites and the texture of `sprite_sheet`, which was loaded again from the
+    // same files. Sprites that are still there keep their ids, new ones are added after them.
+    // Removed sprites keep their ids too, but draw nothing and can't be found by name anymore,
+    // unless this sheet is strict, when the first removed sprite is returned as an error and
+    // the sheet is left as it was.
+    pub fn reload_from(&mut self, sprite_sheet: SpriteSheet) -> Result<(), AssetError> {
+        if self.strict {
+            let removed = self
+                .atlas
+                .sub_textures
+                .iter()
+                .map(|sprite| &sprite.name)
+                .find(|name| {
+                    self.index.contains_key(*name) && !sprite_sheet.index.contains_key(*name)
+                });
+            if let Some(name) = removed {
+                return Err(AssetError::unknown_sprite(name));
+            }
+        }
+
+        let new_index = sprite_sheet.index;
+        let mut sprites: Vec<Option<SubTexture>> = sprite_sheet
+            .atlas
+            .sub_textures
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut sub_textures = Vec::with_capacity(sprites.len());
+        for old in &self.atlas.sub_textures {
+            let sprite = match new_index.get(&old.name) {
+                Some(id) => sprites[id.0].take(),
+                None => None,
+            };
+            sub_textures.push(sprite.unwrap_or_else(|| SubTexture {
+                width: 0.0,
+                height: 0.0,
+                ..old.clone()
+            }));
+        }
+        sub_textures.extend(sprites.into_iter().flatten());
+
+        self.atlas = TextureAtlas {
+            image_path: sprite_sheet.atlas.image_path,
+            sub_textures,
+        };
+        self.index = index_atlas(&self.atlas);
+        self.index.retain(|name, _| new_index.contains_key(name));
+        self.texture = sprite_sheet.texture;
+        self.strict = sprite_sheet.strict;
+        Ok(())
+    }
+
+    // Relative to the descriptor's folder
+    pub fn image_path(&self) -> &str {
+        &self.atlas.image_path
+    }
+
+    pub fn sprite_id(&self, name: &str) -> Option<SpriteId> {
+        self.index.get(name).cloned()
+    }
+
+    pub fn find(&self, name: &str) -> Result<SpriteId, AssetError> {
+        self.sprite_id(name)
+            .ok_or_else(|| AssetError::unknown_sprite(name))
+    }
+
+    // `pos` is the top left corner of the sprite's frame, trimmed sprites are drawn at their
+    // offset inside it, so they end up where the untrimmed sprite would be
+    pub fn render(&self, id: SpriteId, pos: [f64; 2], c: Context, g: &mut dyn Canvas) {
+        self.render_with(id, pos, &DrawParams::default(), c, g);
+    }
+
+    // `pos` is where the anchor of the frame ends up, scaling and rotating keep it in place
+    pub fn render_with(
+        &self,
+        id: SpriteId,
+        pos: [f64; 2],
+        params: &DrawParams,
+        c: Context,
+        g: &mut dyn Canvas,
+    ) {
+        let quad = self.quad(id, pos, params, c);
+        g.image(
+            self.texture.as_ref(),
+            quad.color,
+            quad.src_rect,
+            quad.transform,
+        );
+    }
+
+    // Like `render`, but the sprite is only drawn with the rest of the batch
+    pub fn queue(&self, batch: &mut SpriteBatch, id: SpriteId, pos: [f64; 2], c: Context) {
+        self.queue_with(batch, id, pos, &DrawParams::default(), c);
+    }
 
-        SpriteSheet { atlas, texture }
+    pub fn queue_with(
+        &self,
+        batch: &mut SpriteBatch,
+        id: SpriteId,
+        pos: [f64; 2],
+        params: &DrawParams,
+        c: Context,
+    ) {
+        batch.push(self.quad(id, pos, params, c));
     }
 
-    pub fn render_sprite(&self, name: &String, pos: [f64; 2], c: Context, g: &mut G2d) {
-        let sub_texture = self.atlas.sub_textures.iter().find(|t| t.name == *name);
+    // Looks the sprite up by name like `render_sprite_with` does
+    pub fn queue_sprite_with(
+        &self,
+        batch: &mut SpriteBatch,
+        name: &str,
+        pos: [f64; 2],
+        params: &DrawParams,
+        c: Context,
+    ) {
+        if let Some(id) = self.sprite_id(name) {
+            self.queue_with(batch, id, pos, params, c);
+        }
+    }
 
-        if let Some(sprite) = sub_texture {
-            let src_rect = [sprite.x, sprite.y, sprite.width, sprite.height];
-            Image::new().src_rect(src_rect).draw(
-                &self.texture,
-                &c.draw_state,
-                c.transform.trans(pos[0], pos[1]),
-                g,
-            );
+    // Draws the queued sprites on top of everything drawn so far and empties the batch
+    pub fn draw_batch(&self, batch: &mut SpriteBatch, g: &mut dyn Canvas) {
+        if !batch.is_empty() {
+            g.quads(self.texture.as_ref(), batch.quads());
         }
+        batch.clear();
+    }
+
+    // Size of the sprite's frame, the same as for an untrimmed atlas
+    pub fn size(&self, id: SpriteId) -> [f64; 2] {
+        self.sub_texture(id).frame_size()
+    }
+
+    // Looks the sprite up by name, unknown sprites are not drawn
+    pub fn render_sprite(&self, name: &str, pos: [f64; 2], c: Context, g: &mut dyn Canvas) {
+        self.render_sprite_with(name, pos, &DrawParams::default(), c, g);
     }
 
-    pub fn sprite_size(&self, name: &String) -> Option<[f64; 2]> {
-        self.find_sub_texture(name).map(|s| [s.width, s.height])
+    pub fn render_sprite_with(
+        &self,
+        name: &str,
+        pos: [f64; 2],
+        params: &DrawParams,
+        c: Context,
+        g: &mut dyn Canvas,
+    ) {
+        if let Some(id) = self.sprite_id(name) {
+            self.render_with(id, pos, params, c, g);
+        }
+    }
+
+    pub fn sprite_size(&self, name: &str) -> Option<[f64; 2]> {
+        self.sprite_id(name).map(|id| self.size(id))
+    }
+
+    fn quad(&self, id: SpriteId, pos: [f64; 2], params: &DrawParams, c: Context) -> Quad {
+        let sprite = self.sub_texture(id);
+        let size = sprite.frame_size();
+        let (width, height) = (size[0], size[1]);
+        let mut transform = c
+            .transform
+            .trans(pos[0], pos[1])
+            .rot_rad(params.rotation)
+            .scale(params.scale[0], params.scale[1])
+            .trans(-params.anchor[0] * width, -params.anchor[1] * height);
+        if params.flip_x || params.flip_y {
+            let flip_x = if params.flip_x { -1.0 } else { 1.0 };
+            let flip_y = if params.flip_y { -1.0 } else { 1.0 };
+            transform = transform
+                .trans(width / 2.0, height / 2.0)
+                .scale(flip_x, flip_y)
+                .trans(-width / 2.0, -height / 2.0);
+        }
+        transform = transform.trans(-sprite.frame_x, -sprite.frame_y);
+        if sprite.rotated {
+            // turned back from the 90° clockwise rotation it's stored with in the atlas
+            transform = transform.trans(0.0, sprite.width).rot_deg(-90.0);
+        }
+
+        let src_rect = [sprite.x, sprite.y, sprite.width, sprite.height];
+        let color = [
+            params.tint[0],
+            params.tint[1],
+            params.tint[2],
+            params.tint[3] * params.alpha,
+        ];
+        Quad {
+            color,
+            src_rect,
+            transform,
+        }
     }
 
-    fn find_sub_texture(&self, name: &String) -> Option<&SubTexture> {
-        self.atlas.sub_textures.iter().find(|t| t.name == *name)
+    fn sub_texture(&self, id: SpriteId) -> &SubTexture {
+        &self.atlas.sub_textures[id.0]
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-#[serde(rename_all = "camelCase")]
-struct TextureAtlas {
-    pub image_path: String,
-    #[serde(rename = "SubTexture")]
-    pub sub_textures: Vec<SubTexture>,
+// Sprite names to their position in the atlas, the first one wins if a name is repeated
+#[allow(dead_code)]
+fn index_atlas(atlas: &TextureAtlas) -> HashMap<String, SpriteId> {
+    let mut index = HashMap::with_capacity(atlas.sub_textures.len());
+    for (i, sprite) in atlas.sub_textures.iter().enumerate() {
+        index.entry(sprite.name.clone()).or_insert(SpriteId(i));
+    }
+    index
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
-#[serde(rename_all = "camelCase")]
-struct SubTexture {
-    pub name: String,
-    pub x: f64,
-    pub y: f64,
-    pub width: f64,
-    pub height: f64,
-    pub frame_x: f64,
-    pub frame_y: f64,
-    pub frame_width: f64,
-    pub frame_height: f64,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::canvas::RecordingCanvas;
+
+    fn sheet(names: &[&str]) -> SpriteSheet {
+        let sub_textures = names
+            .iter()
+            .map(|name| SubTexture {
+                name: name.to_string(),
+                x: 0.0,
+                y: 0.0,
+                width: 16.0,
+                height: 16.0,
+                frame_x: 0.0,
+                frame_y: 0.0,
+                frame_width: None,
+                frame_height: None,
+                rotated: false,
+            })
+            .collect();
+        SpriteSheet::from_atlas(
+            TextureAtlas {
+                image_path: "sheet.png".to_string(),
+                sub_textures,
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn reload_keeps_the_ids_of_the_remaining_sprites() {
+        let mut sprite_sheet = sheet(&["a", "b", "c"]);
+        let c = sprite_sheet.find("c").unwrap();
+
+        sprite_sheet.reload_from(sheet(&["d", "c"])).unwrap();
+        assert_eq!(sprite_sheet.sprite_id("c"), Some(c));
+        assert_eq!(sprite_sheet.sprite_id("a"), None);
+        assert!(sprite_sheet.sprite_id("d").is_some());
+    }
+
+    #[test]
+    fn strict_reload_refuses_to_remove_sprites() {
+        let mut sprite_sheet = sheet(&["a", "b", "c"]);
+        sprite_sheet.set_strict(true);
+        let mut reloaded = sheet(&["a", "c"]);
+        reloaded.set_strict(true);
+
+        match sprite_sheet.reload_from(reloaded) {
+            Err(AssetError::UnknownSprite { name }) => assert_eq!(name, "b"),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(sprite_sheet.sprite_id("b").is_some());
+
+        let mut extended = sheet(&["a", "b", "c", "d"]);
+        extended.set_strict(true);
+        assert!(sprite_sheet.reload_from(extended).is_ok());
+    }
+
+    #[test]
+    fn unknown_names_draw_nothing_even_when_strict() {
+        let mut sprite_sheet = sheet(&["a"]);
+        sprite_sheet.set_strict(true);
+        let c = Context::new_abs(64.0, 64.0);
+        let mut canvas = RecordingCanvas::new();
+        let mut batch = SpriteBatch::new();
+
+        sprite_sheet.render_sprite("missing", [0.0, 0.0], c, &mut canvas);
+        sprite_sheet.queue_sprite_with(
+            &mut batch,
+            "missing",
+            [0.0, 0.0],
+            &DrawParams::default(),
+            c,
+        );
+        assert!(canvas.commands.is_empty());
+        assert!(batch.is_empty());
+    }
 }
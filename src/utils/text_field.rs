@@ -0,0 +1,348 @@
+use piston::Button::Keyboard;
+use piston::{ButtonArgs, ButtonState, Key};
+
+// Editable single line of text for consoles and name entry. Feed it the text events for the
+// typed characters and the button events for the editing keys. Positions are in characters.
+#[derive(Clone, Debug, Default)]
+pub struct TextField {
+    text: String,
+    cursor: usize,
+    // The other end of the selection, the cursor is always one end of it
+    anchor: Option<usize>,
+    max_length: Option<usize>,
+    shift: bool,
+    ctrl: bool,
+}
+
+#[allow(dead_code)]
+impl TextField {
+    pub fn new() -> TextField {
+        TextField::default()
+    }
+
+    pub fn set_max_length(&mut self, max_length: usize) -> &mut TextField {
+        self.max_length = Some(max_length);
+        self.truncate(max_length);
+        self
+    }
+
+    pub fn set_text(&mut self, text: &str) -> &mut TextField {
+        self.text = text.to_string();
+        if let Some(max_length) = self.max_length {
+            self.truncate(max_length);
+        }
+        self.cursor = self.len();
+        self.anchor = None;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // Start and end of the selected characters, None if nothing is selected
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.anchor {
+            Some(anchor) if anchor != self.cursor => {
+                Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn selected_text(&self) -> &str {
+        match self.selection() {
+            Some((start, end)) => &self.text[self.byte_index(start)..self.byte_index(end)],
+            None => "",
+        }
+    }
+
+    // Replaces the selection with the text, control characters are skipped
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        let mut inserted: String = text.chars().filter(|c| !c.is_control()).collect();
+        if let Some(max_length) = self.max_length {
+            let room = max_length.saturating_sub(self.len());
+            inserted = inserted.chars().take(room).collect();
+        }
+
+        let index = self.byte_index(self.cursor);
+        self.text.insert_str(index, &inserted);
+        self.cursor += inserted.chars().count();
+    }
+
+    pub fn backspace(&mut self) {
+        if !self.delete_selection() && self.cursor > 0 {
+            self.cursor -= 1;
+            let index = self.byte_index(self.cursor);
+            self.text.remove(index);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if !self.delete_selection() && self.cursor < self.len() {
+            let index = self.byte_index(self.cursor);
+            self.text.remove(index);
+        }
+    }
+
+    // Moving with `select` extends the selection, without it the selection is dropped
+    pub fn move_left(&mut self, select: bool) {
+        let cursor = self.cursor.saturating_sub(1);
+        self.move_cursor(cursor, select);
+    }
+
+    pub fn move_right(&mut self, select: bool) {
+        let cursor = (self.cursor + 1).min(self.len());
+        self.move_cursor(cursor, select);
+    }
+
+    pub fn home(&mut self, select: bool) {
+        self.move_cursor(0, select);
+    }
+
+    pub fn end(&mut self, select: bool) {
+        let cursor = self.len();
+        self.move_cursor(cursor, select);
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.anchor = None;
+    }
+
+    pub fn on_text_event(&mut self, text: &str) {
+        // typing while Ctrl is held is a shortcut, not text
+        if !self.ctrl {
+            self.insert(text);
+        }
+    }
+
+    // Handles the editing keys, returns true if the event was used by the field
+    pub fn on_button_event(&mut self, args: ButtonArgs) -> bool {
+        let key = match args.button {
+            Keyboard(key) => key,
+            _ => return false,
+        };
+        let pressed = args.state == ButtonState::Press;
+        match key {
+            Key::LShift | Key::RShift => self.shift = pressed,
+            Key::LCtrl | Key::RCtrl => self.ctrl = pressed,
+            _ if !pressed => return false,
+            Key::Backspace => self.backspace(),
+            Key::Delete => self.delete(),
+            Key::Left => self.move_left(self.shift),
+            Key::Right => self.move_right(self.shift),
+            Key::Home => self.home(self.shift),
+            Key::End => self.end(self.shift),
+            Key::A if self.ctrl => self.select_all(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn move_cursor(&mut self, cursor: usize, select: bool) {
+        if select {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.cursor);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = cursor;
+    }
+
+    // Returns false if there was nothing selected
+    fn delete_selection(&mut self) -> bool {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => {
+                self.anchor = None;
+                return false;
+            }
+        };
+        let range = self.byte_index(start)..self.byte_index(end);
+        self.text.replace_range(range, "");
+        self.cursor = start;
+        self.anchor = None;
+        true
+    }
+
+    fn truncate(&mut self, max_length: usize) {
+        if self.len() > max_length {
+            let index = self.byte_index(max_length);
+            self.text.truncate(index);
+        }
+        self.cursor = self.cursor.min(max_length);
+        self.anchor = self.anchor.map(|anchor| anchor.min(max_length));
+    }
+
+    fn byte_index(&self, position: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(position)
+            .map(|(index, _)| index)
+            .unwrap_or_else(|| self.text.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(field: &mut TextField, key: Key, state: ButtonState) -> bool {
+        field.on_button_event(ButtonArgs {
+            state,
+            button: Keyboard(key),
+            scancode: None,
+        })
+    }
+
+    fn tap(field: &mut TextField, k: Key) -> bool {
+        let used = key(field, k, ButtonState::Press);
+        key(field, k, ButtonState::Release);
+        used
+    }
+
+    fn field(text: &str) -> TextField {
+        let mut field = TextField::new();
+        field.set_text(text);
+        field
+    }
+
+    #[test]
+    fn inserts_at_the_cursor() {
+        let mut field = field("held");
+        field.move_left(false);
+        field.move_left(false);
+        field.insert("llo wor");
+        assert_eq!(field.text(), "hello world");
+        assert_eq!(field.cursor(), 9);
+
+        field.home(false);
+        field.on_text_event(">");
+        assert_eq!(field.text(), ">hello world");
+    }
+
+    #[test]
+    fn skips_control_characters() {
+        let mut field = TextField::new();
+        field.on_text_event("a\tb\r\n");
+        assert_eq!(field.text(), "ab");
+    }
+
+    #[test]
+    fn backspace_and_delete_stop_at_the_ends() {
+        let mut field = field("abc");
+        tap(&mut field, Key::Delete);
+        assert_eq!(field.text(), "abc");
+        tap(&mut field, Key::Backspace);
+        assert_eq!(field.text(), "ab");
+
+        tap(&mut field, Key::Home);
+        tap(&mut field, Key::Backspace);
+        assert_eq!(field.text(), "ab");
+        tap(&mut field, Key::Delete);
+        assert_eq!(field.text(), "b");
+        assert_eq!(field.cursor(), 0);
+    }
+
+    #[test]
+    fn edits_multibyte_characters_whole() {
+        let mut field = field("héllo wörld");
+        assert_eq!(field.len(), 11);
+        field.move_left(false);
+        field.move_left(false);
+        field.move_left(false);
+        tap(&mut field, Key::Backspace);
+        assert_eq!(field.text(), "héllo wrld");
+
+        field.home(false);
+        field.move_right(false);
+        tap(&mut field, Key::Delete);
+        field.insert("é€");
+        assert_eq!(field.text(), "hé€llo wrld");
+        assert_eq!(field.cursor(), 3);
+    }
+
+    #[test]
+    fn typing_replaces_the_shift_selection() {
+        let mut field = field("one two three");
+        key(&mut field, Key::LShift, ButtonState::Press);
+        for _ in 0..5 {
+            tap(&mut field, Key::Left);
+        }
+        key(&mut field, Key::LShift, ButtonState::Release);
+        assert_eq!(field.selection(), Some((8, 13)));
+        assert_eq!(field.selected_text(), "three");
+
+        field.on_text_event("3");
+        assert_eq!(field.text(), "one two 3");
+        assert_eq!(field.selection(), None);
+
+        // moving without shift drops the selection
+        key(&mut field, Key::RShift, ButtonState::Press);
+        tap(&mut field, Key::Home);
+        key(&mut field, Key::RShift, ButtonState::Release);
+        tap(&mut field, Key::Right);
+        assert_eq!(field.selection(), None);
+        assert_eq!(field.cursor(), 1);
+    }
+
+    #[test]
+    fn ctrl_a_selects_everything_and_is_not_typed() {
+        let mut field = field("text");
+        key(&mut field, Key::LCtrl, ButtonState::Press);
+        assert!(tap(&mut field, Key::A));
+        field.on_text_event("a");
+        key(&mut field, Key::LCtrl, ButtonState::Release);
+        assert_eq!(field.selected_text(), "text");
+
+        tap(&mut field, Key::Backspace);
+        assert!(field.is_empty());
+    }
+
+    #[test]
+    fn truncates_to_the_max_length() {
+        let mut field = field("abcdef");
+        field.set_max_length(4);
+        assert_eq!(field.text(), "abcd");
+        assert_eq!(field.cursor(), 4);
+
+        field.insert("xyz");
+        assert_eq!(field.text(), "abcd");
+
+        field.home(false);
+        field.move_right(true);
+        field.insert("xyz");
+        assert_eq!(field.text(), "xbcd");
+
+        field.set_text("ééééé");
+        assert_eq!(field.text(), "éééé");
+    }
+
+    #[test]
+    fn leaves_other_buttons_alone() {
+        let mut field = TextField::new();
+        assert!(!tap(&mut field, Key::Return));
+        assert!(!key(&mut field, Key::Backspace, ButtonState::Release));
+    }
+}